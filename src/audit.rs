@@ -0,0 +1,173 @@
+use crate::quota::QUOTA_PATH;
+use crate::session::{ROOT_GID, ROOT_UID};
+use crate::types::{Inode, APPEND_ONLY};
+use crate::users::{PASSWD_PATH, SHADOW_PATH};
+use crate::utils;
+
+pub const AUDIT_LOG_PATH: &str = "/var/log/audit";
+
+pub struct Entry {
+    pub timestamp: u64,
+    pub user: String,
+    pub command: String,
+    pub path: Option<String>,
+    pub serial_number: Option<u64>,
+    pub result: String,
+}
+
+impl Entry {
+    fn parse(line: &str) -> Option<Entry> {
+        // Each line of the log is timestamp, user, command, path, serial number and result, separated by tabs,
+        // with '-' for a missing path or serial number
+        let fields: Vec<&str> = line.splitn(6, '\t').collect();
+        if fields.len() != 6 {
+            return None;
+        }
+        Some(Entry {
+            timestamp: fields[0].parse().ok()?,
+            user: unescape(fields[1]),
+            command: unescape(fields[2]),
+            path: Some(unescape(fields[3])).filter(|path| path != "-"),
+            serial_number: fields[4].parse().ok(),
+            result: unescape(fields[5]),
+        })
+    }
+}
+
+fn escape(field: &str) -> String {
+    // Fields are kept on one line, without tabs, so that the log can be split back into entries
+    field.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+fn unescape(field: &str) -> String {
+    let mut result = String::new();
+    let mut characters = field.chars();
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            result.push(character);
+            continue;
+        }
+        match characters.next() {
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+pub fn read_entries(data: &str) -> Vec<Entry> {
+    data.lines().filter_map(Entry::parse).collect()
+}
+
+fn target(commands: &[String]) -> Option<String> {
+    // Path changed by the command, or None if the command doesn't change the filesystem
+    let arguments = &commands[1..];
+    let path = match commands[0].as_str() {
+        "sudo" if !arguments.is_empty() => return target(arguments),
+        "new" | "readonly" | "immutable" => arguments.get(1),
+        "edit" | "remove" | "rename" | "move" | "touch" | "write" | "encrypt" | "decrypt" | ">" | ">>" => arguments.first(),
+        "chmod" | "chown" | "chgrp" => arguments.iter().skip_while(|argument| *argument == "-R").nth(1),
+        "setfacl" => {
+            let mut index = 0;
+            while index < arguments.len() && arguments[index].starts_with('-') {
+                index += if arguments[index] == "-m" || arguments[index] == "-x" { 2 } else { 1 };
+            }
+            arguments.get(index)
+        },
+        "useradd" | "userdel" => return Some(String::from(PASSWD_PATH)),
+        "passwd" => return Some(String::from(SHADOW_PATH)),
+        "setquota" if arguments.first().is_some_and(|argument| argument == "-d") => arguments.get(1),
+        "setquota" => return Some(String::from(QUOTA_PATH)),
+        _ => None,
+    };
+    path.cloned()
+}
+
+fn logged_command(commands: &[String]) -> String {
+    // Command line written to the log, without the text written to files so that contents stay out of it
+    match commands[0].as_str() {
+        "sudo" if commands.len() > 1 => format!("sudo {}", logged_command(&commands[1..])),
+        "write" => commands.iter().take(2).cloned().collect::<Vec<String>>().join(" "),
+        _ => commands.join(" "),
+    }
+}
+
+fn serial_number(root: &Inode, path: &str) -> Option<u64> {
+    root.get_inode_by_path(&utils::normalize_path(path)).map(Inode::get_serial_number)
+}
+
+pub fn audited(root: &mut Inode, user: &str, commands: &[String], run: impl FnOnce(&mut Inode) -> Result<(), String>) -> Result<(), String> {
+    // Run the command, recording it in the audit log if it changes the filesystem or can elevate privileges.
+    // The serial number is the one of the target before the command, or after it when the target is created
+    let path = target(commands);
+    if path.is_none() && !matches!(commands[0].as_str(), "sudo" | "login") {
+        return run(root);
+    }
+    let previous_serial_number = path.as_deref().and_then(|path| serial_number(root, path));
+    let result = run(root);
+    let entry = Entry {
+        timestamp: utils::now_date(),
+        user: user.to_string(),
+        command: logged_command(commands),
+        serial_number: previous_serial_number.or_else(|| path.as_deref().and_then(|path| serial_number(root, path))),
+        path: path.map(|path| format!("/{}", utils::normalize_path(&path).join("/"))),
+        result: match &result {
+            Ok(()) => String::from("ok"),
+            Err(error) => format!("error: {}", error),
+        },
+    };
+    record(root, &entry);
+    result
+}
+
+fn record(root: &mut Inode, entry: &Entry) {
+    // Append the entry to the log, which is owned by root, only readable by it and append-only.
+    // A missing log directory disables the audit
+    let line = format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\n",
+        entry.timestamp,
+        escape(&entry.user),
+        escape(&entry.command),
+        escape(entry.path.as_deref().unwrap_or("-")),
+        entry.serial_number.map_or(String::from("-"), |serial_number| serial_number.to_string()),
+        escape(&entry.result)
+    );
+    let (parent_path, name) = utils::split_path(AUDIT_LOG_PATH).expect("Invalid audit log path");
+    if let Some(directory) = root.get_inode_by_path_mut(&parent_path) {
+        if directory.write_file(&name, &line, true, ROOT_UID, ROOT_GID).is_ok() {
+            if let Some(inode) = directory.get_inode_by_name_mut(&name) {
+                inode.set_permissions(0o600);
+                inode.set_attribute(APPEND_ONLY, true);
+            }
+        }
+    }
+    root.refresh_sizes(&parent_path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{audited, read_entries, AUDIT_LOG_PATH};
+    use crate::session::{ROOT_GID, ROOT_UID};
+    use crate::types::{Inode, DIR_MODE};
+    use crate::users;
+    use crate::utils;
+
+    #[test]
+    fn entries_keep_file_contents_out_and_fields_on_one_line() {
+        let mut root = Inode::new(DIR_MODE, String::from("/"), ROOT_UID, ROOT_GID);
+        users::bootstrap(&mut root);
+        let commands: Vec<String> = ["sudo", "write", "/tmp/a\tb", "secret", "text"].map(String::from).to_vec();
+        assert!(audited(&mut root, "user", &commands, |_| Err(String::from("first\nsecond"))).is_err());
+
+        let data = root.get_inode_by_path(&utils::normalize_path(AUDIT_LOG_PATH)).unwrap().read_content(None).unwrap();
+        assert!(!data.contains("secret"));
+        assert_eq!(data.lines().count(), 1);
+        let entries = read_entries(&data);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].command, "sudo write /tmp/a\tb");
+        assert_eq!(entries[0].path.as_deref(), Some("/tmp/a\tb"));
+        assert_eq!(entries[0].result, "error: first\nsecond");
+    }
+}
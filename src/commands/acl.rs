@@ -0,0 +1,306 @@
+use std::io::Write;
+use crate::session::Session;
+use crate::types::{AclEntry, AclTag, Inode, READ};
+use crate::users;
+use crate::utils;
+use super::permissions::{apply, check_owner, parse_group, parse_user, split_recursive};
+
+enum BaseTag {
+    Owner,
+    OwnerGroup,
+    Other,
+}
+
+enum Target {
+    Base(BaseTag),
+    Named(AclTag),
+}
+
+fn rwx(bits: u16) -> String {
+    format!(
+        "{}{}{}",
+        if bits & 0o4 != 0 { 'r' } else { '-' },
+        if bits & 0o2 != 0 { 'w' } else { '-' },
+        if bits & 0o1 != 0 { 'x' } else { '-' }
+    )
+}
+
+fn parse_rwx(value: &str) -> Result<u16, String> {
+    let mut bits: u16 = 0;
+    for c in value.chars() {
+        bits |= match c {
+            'r' => 0o4,
+            'w' => 0o2,
+            'x' => 0o1,
+            '-' => 0,
+            _ => return Err(format!("Invalid permissions '{}'", value)),
+        };
+    }
+    Ok(bits)
+}
+
+fn parse_target(root_inode: &Inode, tag: &str, qualifier: &str) -> Result<Target, String> {
+    // Entries are like u:name, g:name or m:, and u:, g: or o: for the base permissions
+    match (tag, qualifier) {
+        ("u" | "user", "") => Ok(Target::Base(BaseTag::Owner)),
+        ("g" | "group", "") => Ok(Target::Base(BaseTag::OwnerGroup)),
+        ("o" | "other", "") => Ok(Target::Base(BaseTag::Other)),
+        ("m" | "mask", "") => Ok(Target::Named(AclTag::Mask)),
+        ("u" | "user", name) => Ok(Target::Named(AclTag::User(parse_user(root_inode, name)?))),
+        ("g" | "group", name) => Ok(Target::Named(AclTag::Group(parse_group(root_inode, name)?))),
+        _ => Err(format!("Invalid ACL entry '{}:{}'", tag, qualifier)),
+    }
+}
+
+enum Change {
+    Modify(Target, u16),
+    Remove(Target),
+}
+
+fn parse_changes(root_inode: &Inode, spec: &str, remove: bool) -> Result<Vec<Change>, String> {
+    let mut changes = Vec::new();
+    for entry in spec.split(',') {
+        let fields: Vec<&str> = entry.split(':').collect();
+        if remove {
+            if fields.len() > 2 {
+                return Err(format!("Invalid ACL entry '{}'", entry));
+            }
+            changes.push(Change::Remove(parse_target(root_inode, fields[0], fields.get(1).unwrap_or(&""))?));
+        } else {
+            if fields.len() != 3 {
+                return Err(format!("Invalid ACL entry '{}'", entry));
+            }
+            changes.push(Change::Modify(parse_target(root_inode, fields[0], fields[1])?, parse_rwx(fields[2])?));
+        }
+    }
+    Ok(changes)
+}
+
+fn recalculate_mask(acl: &mut Vec<AclEntry>, permissions: u16) {
+    // Like setfacl, the mask becomes the union of the owner group and named entries, and goes away with them
+    acl.retain(|entry| entry.tag != AclTag::Mask);
+    if acl.is_empty() {
+        return;
+    }
+    let mask = acl.iter().fold((permissions >> 3) & 0o7, |mask, entry| mask | entry.permissions);
+    acl.push(AclEntry { tag: AclTag::Mask, permissions: mask });
+}
+
+fn apply_changes(inode: &mut Inode, changes: &[Change], default: bool) {
+    let mut acl = if default { inode.get_default_acl().to_vec() } else { inode.get_acl().to_vec() };
+    let mut permissions = inode.get_permissions();
+    let mut mask_set = false;
+    for change in changes {
+        match change {
+            Change::Modify(Target::Named(tag), bits) => {
+                mask_set |= *tag == AclTag::Mask;
+                match acl.iter_mut().find(|entry| entry.tag == *tag) {
+                    Some(entry) => entry.permissions = *bits,
+                    None => acl.push(AclEntry { tag: *tag, permissions: *bits }),
+                }
+            },
+            Change::Remove(Target::Named(tag)) => acl.retain(|entry| entry.tag != *tag),
+            // The base entries are the permission bits of the inode
+            Change::Modify(Target::Base(base), bits) => {
+                let shift = match base {
+                    BaseTag::Owner => 6,
+                    BaseTag::OwnerGroup => 3,
+                    BaseTag::Other => 0,
+                };
+                permissions = (permissions & !(0o7 << shift)) | (bits << shift);
+            },
+            _ => {},
+        }
+    }
+    if !mask_set {
+        recalculate_mask(&mut acl, permissions);
+    }
+    if default {
+        inode.set_default_acl(acl);
+    } else {
+        inode.set_permissions(permissions);
+        inode.set_acl(acl);
+    }
+}
+
+pub fn handle_setfacl(commands: Vec<String>, root_inode: &mut Inode, session: &Session) -> Result<(), String> {
+    let (recursive, arguments) = split_recursive(&commands);
+    let mut default = false;
+    let mut remove_all = false;
+    let mut remove_default = false;
+    let mut specs: Vec<(String, bool)> = Vec::new();
+    let mut path: Option<String> = None;
+    let mut index = 0;
+    while index < arguments.len() {
+        match arguments[index].as_str() {
+            "-d" => default = true,
+            "-b" => remove_all = true,
+            "-k" => remove_default = true,
+            "-m" | "-x" => {
+                let spec = arguments.get(index + 1).ok_or("Missing ACL entries after -m or -x")?;
+                specs.push((spec.clone(), arguments[index] == "-x"));
+                index += 1;
+            },
+            argument if path.is_none() && !argument.starts_with('-') => path = Some(argument.to_string()),
+            _ => return Err("Invalid arguments, type 'help setfacl' to see the usage of the command".into()),
+        }
+        index += 1;
+    }
+    let path = path.ok_or("Missing path, type 'help setfacl' to see the usage of the command")?;
+    let mut changes: Vec<Change> = Vec::new();
+    for (spec, remove) in &specs {
+        changes.extend(parse_changes(root_inode, spec, *remove)?);
+    }
+
+    let inode = session.resolve_mut(root_inode, &utils::normalize_path(&path))?;
+    check_owner(session, inode, &path, recursive)?;
+    if default && !inode.is_directory() {
+        return Err("Only directories can have a default ACL".into());
+    }
+    // The base entries are the permission bits, a default ACL only holds named entries and the mask
+    if default && changes.iter().any(|change| matches!(change, Change::Modify(Target::Base(_), _) | Change::Remove(Target::Base(_)))) {
+        return Err("A default ACL can only have named user, named group and mask entries".into());
+    }
+    apply(inode, recursive, &mut |inode: &mut Inode| {
+        if remove_all {
+            inode.set_acl(Vec::new());
+            inode.set_default_acl(Vec::new());
+        }
+        if remove_default && inode.is_directory() {
+            inode.set_default_acl(Vec::new());
+        }
+        if !default || inode.is_directory() {
+            apply_changes(inode, &changes, default);
+        }
+    });
+    Ok(())
+}
+
+fn write_entries(output: &mut dyn Write, root_inode: &Inode, acl: &[AclEntry], prefix: &str) {
+    for entry in acl {
+        match entry.tag {
+            AclTag::User(uid) => writeln!(output, "{}user:{}:{}", prefix, users::user_name(root_inode, uid), rwx(entry.permissions)).unwrap(),
+            AclTag::Group(gid) => writeln!(output, "{}group:{}:{}", prefix, users::group_name(root_inode, gid), rwx(entry.permissions)).unwrap(),
+            AclTag::Mask => writeln!(output, "{}mask::{}", prefix, rwx(entry.permissions)).unwrap(),
+        }
+    }
+}
+
+pub fn handle_getfacl(commands: Vec<String>, root_inode: &mut Inode, session: &Session, output: &mut dyn Write) -> Result<(), String> {
+    if commands.len() != 1 {
+        return Err("Invalid number of arguments, type 'help getfacl' to see the usage of the command".into());
+    }
+    let inode = session.resolve(root_inode, &utils::normalize_path(&commands[0]))?;
+    session.check_access(inode, READ)?;
+    let permissions = inode.get_permissions();
+    writeln!(output, "# file: {}", commands[0]).unwrap();
+    writeln!(output, "# owner: {}", users::user_name(root_inode, inode.get_uid())).unwrap();
+    writeln!(output, "# group: {}", users::group_name(root_inode, inode.get_gid())).unwrap();
+    writeln!(output, "user::{}", rwx(permissions >> 6)).unwrap();
+    let (named, mask): (Vec<AclEntry>, Vec<AclEntry>) = inode.get_acl().iter().partition(|entry| entry.tag != AclTag::Mask);
+    write_entries(output, root_inode, &named.iter().filter(|entry| matches!(entry.tag, AclTag::User(_))).copied().collect::<Vec<_>>(), "");
+    writeln!(output, "group::{}", rwx(permissions >> 3)).unwrap();
+    write_entries(output, root_inode, &named.iter().filter(|entry| matches!(entry.tag, AclTag::Group(_))).copied().collect::<Vec<_>>(), "");
+    write_entries(output, root_inode, &mask, "");
+    writeln!(output, "other::{}", rwx(permissions)).unwrap();
+    write_entries(output, root_inode, inode.get_default_acl(), "default:");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_changes, parse_changes};
+    use crate::session::Session;
+    use crate::types::{AclEntry, AclTag, Inode, DIR_MODE, READ, WRITE};
+    use crate::users::User;
+
+    fn session(uid: u32, groups: Vec<u32>) -> Session {
+        let user = User {
+            name: format!("user{}", uid),
+            uid,
+            gid: uid,
+            home: String::new(),
+        };
+        Session::for_user(&user, groups)
+    }
+
+    fn setfacl(inode: &mut Inode, spec: &str, remove: bool, default: bool) {
+        let root = Inode::new(DIR_MODE, String::from("/"), 0, 0);
+        let changes = parse_changes(&root, spec, remove).unwrap();
+        apply_changes(inode, &changes, default);
+    }
+
+    fn file(permissions: u16) -> Inode {
+        let mut inode = Inode::new_file_with_data(String::from("f"), String::new(), 1000, 1000);
+        inode.set_permissions(permissions);
+        inode
+    }
+
+    #[test]
+    fn entries_are_evaluated_in_order() {
+        // The owner bits win over a named entry for the owner, and a named entry over the groups of the user
+        let mut inode = file(0o640);
+        setfacl(&mut inode, "u:1000:---,u:2000:---", false, false);
+        assert!(session(1000, vec![1000]).can_access(&inode, READ | WRITE));
+        assert!(!session(2000, vec![2000, 1000]).can_access(&inode, READ));
+        assert!(session(3000, vec![3000, 1000]).can_access(&inode, READ));
+
+        // A matching group that denies the access stops there, others aren't looked at
+        let mut inode = file(0o604);
+        setfacl(&mut inode, "g:4000:rw-", false, false);
+        assert!(!session(2000, vec![2000, 1000]).can_access(&inode, READ));
+        assert!(session(2000, vec![2000, 1000, 4000]).can_access(&inode, READ | WRITE));
+        assert!(session(3000, vec![3000]).can_access(&inode, READ));
+    }
+
+    #[test]
+    fn mask_follows_the_entries_unless_set() {
+        let mut inode = file(0o640);
+        setfacl(&mut inode, "u:2000:rw-", false, false);
+        assert_eq!(inode.acl_mask(), 0o6);
+        setfacl(&mut inode, "g:3000:--x", false, false);
+        assert_eq!(inode.acl_mask(), 0o7);
+
+        // An explicit mask limits named entries and the owner group, but not the owner
+        setfacl(&mut inode, "m::r--", false, false);
+        assert_eq!(inode.acl_mask(), 0o4);
+        assert!(session(2000, vec![2000]).can_access(&inode, READ));
+        assert!(!session(2000, vec![2000]).can_access(&inode, WRITE));
+        assert!(session(1000, vec![1000]).can_access(&inode, WRITE));
+
+        // Removing the last named entries removes the mask with them
+        setfacl(&mut inode, "u:2000,g:3000", true, false);
+        assert!(inode.get_acl().is_empty());
+        assert_eq!(inode.get_permissions(), 0o640);
+    }
+
+    #[test]
+    fn default_entries_are_inherited() {
+        let mut directory = Inode::new(DIR_MODE, String::from("d"), 1000, 1000);
+        setfacl(&mut directory, "g:3000:rwx", false, true);
+        assert!(directory.get_acl().is_empty());
+        let entries = vec![AclEntry { tag: AclTag::Group(3000), permissions: 0o7 }, AclEntry { tag: AclTag::Mask, permissions: 0o7 }];
+        assert_eq!(directory.get_default_acl(), entries.as_slice());
+
+        // Files get the entries, directories also pass them on
+        let mut private = file(0o600);
+        private.set_name(String::from("p"));
+        directory.add_inode(file(0o640));
+        directory.add_inode(private);
+        directory.add_inode(Inode::new(DIR_MODE, String::from("sub"), 1000, 1000));
+        let file = directory.get_inode_by_name("f").unwrap();
+        let subdirectory = directory.get_inode_by_name("sub").unwrap();
+        assert_eq!(file.get_acl()[0], entries[0]);
+        assert!(file.get_default_acl().is_empty());
+        assert_eq!(subdirectory.get_acl()[0], entries[0]);
+        assert_eq!(subdirectory.get_default_acl(), entries.as_slice());
+
+        // The group bits of the mode limit the inherited mask
+        assert_eq!((file.acl_mask(), subdirectory.acl_mask()), (0o4, 0o5));
+        assert!(session(2000, vec![2000, 3000]).can_access(&file, READ));
+        assert!(!session(2000, vec![2000, 3000]).can_access(&file, WRITE));
+        let private = directory.get_inode_by_name("p").unwrap();
+        assert_eq!(private.acl_mask(), 0);
+        assert!(!session(2000, vec![2000, 3000]).can_access(&private, READ));
+    }
+}
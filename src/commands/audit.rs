@@ -0,0 +1,61 @@
+use std::io::Write;
+use crate::audit::{self, AUDIT_LOG_PATH};
+use crate::session::Session;
+use crate::types::Inode;
+use crate::utils;
+
+fn parse_since(value: &str) -> Result<u64, &'static str> {
+    // A timestamp in seconds since the epoch, or a duration before now such as 30s, 15m, 2h or 1d
+    let error = "Invalid time, expected seconds since the epoch or a duration like 30s, 15m, 2h or 1d";
+    let unit = match value.chars().last() {
+        Some('s') => 1,
+        Some('m') => 60,
+        Some('h') => 60 * 60,
+        Some('d') => 24 * 60 * 60,
+        _ => return value.parse().map_err(|_| error),
+    };
+    let amount: u64 = value[..value.len() - 1].parse().map_err(|_| error)?;
+    Ok(utils::now_date().saturating_sub(amount.saturating_mul(unit)))
+}
+
+pub fn handle_audit(commands: Vec<String>, root_inode: &mut Inode, session: &mut Session, output: &mut dyn Write) -> Result<(), String> {
+    let mut since: Option<u64> = None;
+    let mut path: Option<Vec<String>> = None;
+    let mut index = 0;
+    while index < commands.len() {
+        let value = commands.get(index + 1);
+        match (commands[index].as_str(), value) {
+            ("--since", Some(value)) => since = Some(parse_since(value)?),
+            ("--path", Some(value)) => path = Some(utils::normalize_path(value)),
+            _ => return Err("Invalid arguments, type 'help audit' to see the usage of the command".into()),
+        }
+        index += 2;
+    }
+
+    // The log is only readable by root, so the usual read permission check applies
+    let data = session.read_file(root_inode, AUDIT_LOG_PATH)?;
+    for entry in audit::read_entries(&data) {
+        if since.is_some_and(|since| entry.timestamp < since) {
+            continue;
+        }
+        if let Some(path) = &path {
+            // Entries of the path itself or of anything inside it
+            let matches = entry.path.as_deref().is_some_and(|entry_path| utils::normalize_path(entry_path).starts_with(path));
+            if !matches {
+                continue;
+            }
+        }
+        writeln!(
+            output,
+            "{} {:<8} {:<24} {:>6} {} => {}",
+            entry.timestamp,
+            entry.user,
+            entry.path.as_deref().unwrap_or("-"),
+            entry.serial_number.map_or(String::from("-"), |serial_number| serial_number.to_string()),
+            entry.command,
+            entry.result
+        )
+        .unwrap();
+    }
+    Ok(())
+}
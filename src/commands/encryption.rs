@@ -0,0 +1,44 @@
+use crate::quota::{self, Usage};
+use crate::session::Session;
+use crate::types::{Inode, READ, WRITE};
+use crate::utils;
+
+fn change_encryption(commands: Vec<String>, root_inode: &mut Inode, session: &mut Session, encrypt: bool) -> Result<(), String> {
+    if commands.len() != 1 {
+        let usage = if encrypt { "encrypt" } else { "decrypt" };
+        return Err(format!("Invalid number of arguments, type 'help {}' to see the usage of the command", usage));
+    }
+    let path = &commands[0];
+    let passphrase = if encrypt { session.new_passphrase()? } else { session.passphrase()? };
+    let (parent_path, _) = utils::split_path(path)?;
+    let (parent_inode, name) = session.resolve_parent_mut(root_inode, path)?;
+    let inode = parent_inode.get_inode_by_name(&name).ok_or("File not found")?;
+    if !inode.is_file() {
+        return Err(format!("'{}' is not a file", path));
+    }
+    session.check_access(&inode, READ | WRITE)?;
+    inode.check_protected(path)?;
+    // The content is rewritten on a copy first, so that the quotas are checked against its new size
+    let mut changed = inode.clone();
+    let result = if encrypt { changed.encrypt(&passphrase) } else { changed.decrypt(&passphrase) };
+    result.map_err(|error| format!("{} for '{}'", error, path))?;
+    let bytes = changed.content_size().saturating_sub(inode.content_size());
+    let warnings = quota::check(root_inode, inode.get_uid(), &parent_path, Usage { bytes, inodes: 0 })?;
+    session.warnings.extend(warnings);
+    if let Some(parent_inode) = root_inode.get_inode_by_path_mut(&parent_path) {
+        parent_inode.update_child(&name, |inode| *inode = changed);
+    }
+    root_inode.refresh_sizes(&parent_path);
+    if !encrypt {
+        session.passphrase = Some(passphrase);
+    }
+    Ok(())
+}
+
+pub fn handle_encrypt(commands: Vec<String>, root_inode: &mut Inode, session: &mut Session) -> Result<(), String> {
+    change_encryption(commands, root_inode, session, true)
+}
+
+pub fn handle_decrypt(commands: Vec<String>, root_inode: &mut Inode, session: &mut Session) -> Result<(), String> {
+    change_encryption(commands, root_inode, session, false)
+}
@@ -0,0 +1,608 @@
+use std::fmt;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+const MIN_GAP: usize = 64;
+const TAB_WIDTH: usize = 4;
+const MAX_GRAPHEME: usize = 32; // characters looked at around an offset to find the grapheme cluster next to it
+
+pub fn grapheme_width(grapheme: &str) -> usize {
+    // Columns taken on the terminal by a grapheme cluster, tabs being drawn as spaces
+    if grapheme == "\t" {
+        TAB_WIDTH
+    } else {
+        grapheme.width()
+    }
+}
+
+pub fn display_width(text: &str) -> usize {
+    text.graphemes(true).map(grapheme_width).sum()
+}
+
+pub struct GapBuffer {
+    // The text is buffer[..gap_start] followed by buffer[gap_end..], the cursor is at the gap.
+    // Editing at the cursor never moves the rest of the text, only moving the cursor does.
+    // Offsets and columns count characters, the cursor moves over whole grapheme clusters
+    buffer: Vec<char>,
+    gap_start: usize,
+    gap_end: usize,
+    line_index: usize,
+    col_index: usize,
+    line_count: usize,
+}
+
+impl GapBuffer {
+    pub fn new() -> GapBuffer {
+        GapBuffer {
+            buffer: vec!['\0'; MIN_GAP],
+            gap_start: 0,
+            gap_end: MIN_GAP,
+            line_index: 0,
+            col_index: 0,
+            line_count: 1,
+        }
+    }
+
+    pub fn from_string(value: &str) -> GapBuffer {
+        // The cursor starts at the beginning of the text, so the whole gap is in front of it
+        let text: Vec<char> = value.chars().collect();
+        let line_count = 1 + text.iter().filter(|c| **c == '\n').count();
+        let mut buffer = vec!['\0'; MIN_GAP];
+        buffer.extend(text);
+        GapBuffer {
+            buffer,
+            gap_start: 0,
+            gap_end: MIN_GAP,
+            line_index: 0,
+            col_index: 0,
+            line_count,
+        }
+    }
+
+    pub fn line_index(&self) -> usize {
+        self.line_index
+    }
+
+    pub fn col_index(&self) -> usize {
+        self.col_index
+    }
+
+    pub fn display_col(&self) -> usize {
+        // Terminal column of the cursor in its line, from the width of the grapheme clusters before it
+        display_width(&self.slice(self.gap_start - self.col_index, self.gap_start))
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_count
+    }
+
+    pub fn position(&self) -> usize {
+        // Offset of the cursor in the text, in characters
+        self.gap_start
+    }
+
+    pub fn set_position(&mut self, position: usize) {
+        // Move the cursor to the offset in the text, or to its end, keeping track of its line and column
+        while self.gap_start > position {
+            if self.step_back() == Some('\n') {
+                self.line_index -= 1;
+            }
+        }
+        while self.gap_start < position {
+            if self.step_forward().is_none() {
+                break;
+            }
+            if self.buffer[self.gap_start - 1] == '\n' {
+                self.line_index += 1;
+            }
+        }
+        self.col_index = self.buffer[..self.gap_start].iter().rev().take_while(|c| **c != '\n').count();
+    }
+
+    pub fn char_count(&self) -> usize {
+        self.buffer.len() - (self.gap_end - self.gap_start)
+    }
+
+    fn char_at(&self, index: usize) -> char {
+        // Character at the offset in the text, skipping the gap
+        if index < self.gap_start {
+            self.buffer[index]
+        } else {
+            self.buffer[index + self.gap_end - self.gap_start]
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<char> {
+        (index < self.char_count()).then(|| self.char_at(index))
+    }
+
+    pub fn slice(&self, start: usize, end: usize) -> String {
+        // Text between the two offsets, which are kept inside the text
+        (start..end.min(self.char_count())).map(|index| self.char_at(index)).collect()
+    }
+
+    pub fn lines(&self, first: usize, count: usize) -> Vec<String> {
+        // Up to count lines of the text from the first one. They are found from the line of the cursor,
+        // so the cost depends on the lines in between and not on the size of the text
+        let length = self.char_count();
+        let mut start = self.gap_start - self.col_index;
+        let mut line = self.line_index;
+        while line > first {
+            start -= 1;
+            while start > 0 && self.char_at(start - 1) != '\n' {
+                start -= 1;
+            }
+            line -= 1;
+        }
+        while line < first {
+            while start < length && self.char_at(start) != '\n' {
+                start += 1;
+            }
+            if start == length {
+                return Vec::new();
+            }
+            start += 1;
+            line += 1;
+        }
+        let mut lines = Vec::new();
+        let mut current = String::new();
+        for index in start..=length {
+            if lines.len() == count {
+                break;
+            }
+            if index == length || self.char_at(index) == '\n' {
+                lines.push(std::mem::take(&mut current));
+            } else {
+                current.push(self.char_at(index));
+            }
+        }
+        lines
+    }
+
+    pub fn find(&self, pattern: &str, from: usize, forward: bool) -> Option<usize> {
+        // Offset of the first match starting at or after from, or going backward of the last one starting before it
+        let pattern: Vec<char> = pattern.chars().collect();
+        let length = self.char_count();
+        if pattern.is_empty() || pattern.len() > length {
+            return None;
+        }
+        let last = length - pattern.len();
+        let matches = |start: &usize| pattern.iter().enumerate().all(|(i, c)| self.char_at(start + i) == *c);
+        if forward {
+            (from..=last).find(matches)
+        } else {
+            (0..from.min(last + 1)).rev().find(matches)
+        }
+    }
+
+    pub fn previous_grapheme(&self, position: usize) -> usize {
+        // Offset of the beginning of the grapheme cluster before the offset, in the same line
+        let before = self.slice(position.saturating_sub(MAX_GRAPHEME), position);
+        let before = before.rsplit('\n').next().unwrap_or_default();
+        before.graphemes(true).next_back().map_or(position, |grapheme| position - grapheme.chars().count())
+    }
+
+    pub fn next_grapheme(&self, position: usize) -> usize {
+        // Offset of the end of the grapheme cluster after the offset, in the same line
+        let after = self.slice(position, position + MAX_GRAPHEME);
+        let after = after.split('\n').next().unwrap_or_default();
+        after.graphemes(true).next().map_or(position, |grapheme| position + grapheme.chars().count())
+    }
+
+    fn char_before(&self) -> Option<char> {
+        if self.gap_start > 0 {
+            Some(self.buffer[self.gap_start - 1])
+        } else {
+            None
+        }
+    }
+
+    fn char_after(&self) -> Option<char> {
+        self.buffer.get(self.gap_end).copied()
+    }
+
+    fn grow(&mut self) {
+        // Double the buffer, moving the text after the gap to the end of the new space
+        let added = self.buffer.len().max(MIN_GAP);
+        let after = self.buffer.len() - self.gap_end;
+        self.buffer.resize(self.buffer.len() + added, '\0');
+        self.buffer.copy_within(self.gap_end..self.gap_end + after, self.gap_end + added);
+        self.gap_end += added;
+    }
+
+    fn step_back(&mut self) -> Option<char> {
+        // Move the cursor one character back, without updating the line and column
+        let c = self.char_before()?;
+        self.gap_start -= 1;
+        self.gap_end -= 1;
+        self.buffer[self.gap_end] = c;
+        Some(c)
+    }
+
+    fn step_forward(&mut self) -> Option<char> {
+        // Move the cursor one character forward, without updating the line and column
+        let c = self.char_after()?;
+        self.buffer[self.gap_start] = c;
+        self.gap_start += 1;
+        self.gap_end += 1;
+        Some(c)
+    }
+
+    fn advance_to_width(&mut self, target: usize) {
+        // Move forward over the grapheme clusters that fit in the target width from the cursor, in its line
+        let mut width = 0;
+        loop {
+            let next = self.next_grapheme(self.gap_start);
+            let grapheme = self.slice(self.gap_start, next);
+            if grapheme.is_empty() || width + grapheme_width(&grapheme) > target {
+                break;
+            }
+            width += grapheme_width(&grapheme);
+            self.col_index += next - self.gap_start;
+            while self.gap_start < next {
+                self.step_forward();
+            }
+        }
+    }
+
+    fn line_length_after(&self) -> usize {
+        // Characters between the cursor and the end of its line
+        self.buffer[self.gap_end..].iter().take_while(|c| **c != '\n').count()
+    }
+
+    pub fn push(&mut self, c: char) {
+        if self.gap_start == self.gap_end {
+            self.grow();
+        }
+        self.buffer[self.gap_start] = c;
+        self.gap_start += 1;
+        if c == '\n' {
+            self.line_index += 1;
+            self.col_index = 0;
+            self.line_count += 1;
+        } else {
+            self.col_index += 1;
+        }
+    }
+
+    pub fn push_str(&mut self, text: &str) {
+        for c in text.chars() {
+            self.push(c);
+        }
+    }
+
+    pub fn remove(&mut self) -> Option<String> {
+        // Remove the grapheme cluster before the cursor, or join the line with the previous one at its beginning
+        if self.char_before()? == '\n' {
+            self.gap_start -= 1;
+            self.line_index -= 1;
+            self.line_count -= 1;
+            self.col_index = self.buffer[..self.gap_start].iter().rev().take_while(|c| **c != '\n').count();
+            return Some(String::from("\n"));
+        }
+        let start = self.previous_grapheme(self.gap_start);
+        let removed = self.slice(start, self.gap_start);
+        self.col_index -= self.gap_start - start;
+        self.gap_start = start;
+        Some(removed)
+    }
+
+    pub fn delete_forward(&mut self, count: usize) -> String {
+        // Remove up to count characters after the cursor, returning them
+        let end = (self.gap_end + count).min(self.buffer.len());
+        let removed: String = self.buffer[self.gap_end..end].iter().collect();
+        self.line_count -= removed.matches('\n').count();
+        self.gap_end = end;
+        removed
+    }
+
+    pub fn move_left(&mut self) {
+        // This function will move the cursor to the left, over a whole grapheme cluster
+        // If it is in the first column, it will move to the end of the previous line
+        if self.col_index == 0 {
+            if self.step_back().is_some() {
+                self.line_index -= 1;
+                self.col_index = self.buffer[..self.gap_start].iter().rev().take_while(|c| **c != '\n').count();
+            }
+        } else {
+            let start = self.previous_grapheme(self.gap_start);
+            self.col_index -= self.gap_start - start;
+            while self.gap_start > start {
+                self.step_back();
+            }
+        }
+    }
+
+    pub fn move_right(&mut self) {
+        // This function will move the cursor to the right, over a whole grapheme cluster
+        // If it is in the last column, it will move to the beginning of the next line
+        match self.char_after() {
+            Some('\n') => {
+                self.step_forward();
+                self.line_index += 1;
+                self.col_index = 0;
+            },
+            Some(_) => {
+                let end = self.next_grapheme(self.gap_start);
+                self.col_index += end - self.gap_start;
+                while self.gap_start < end {
+                    self.step_forward();
+                }
+            },
+            None => {},
+        }
+    }
+
+    pub fn move_up(&mut self) {
+        // This function will move the cursor up if it isn't in the first line,
+        // keeping the terminal column when the previous line is long enough
+        if self.line_index == 0 {
+            return;
+        }
+        let target = self.display_col();
+        for _ in 0..=self.col_index {
+            self.step_back();
+        }
+        let previous_length = self.buffer[..self.gap_start].iter().rev().take_while(|c| **c != '\n').count();
+        for _ in 0..previous_length {
+            self.step_back();
+        }
+        self.line_index -= 1;
+        self.col_index = 0;
+        self.advance_to_width(target);
+    }
+
+    pub fn move_down(&mut self) {
+        // This function will move the cursor down if it isn't in the last line,
+        // keeping the terminal column when the next line is long enough
+        let rest = self.line_length_after();
+        if self.gap_end + rest == self.buffer.len() {
+            return;
+        }
+        let target = self.display_col();
+        for _ in 0..=rest {
+            self.step_forward();
+        }
+        self.line_index += 1;
+        self.col_index = 0;
+        self.advance_to_width(target);
+    }
+}
+
+impl fmt::Display for GapBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.buffer[..self.gap_start].iter().chain(&self.buffer[self.gap_end..]) {
+            write!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GapBuffer;
+
+    fn cursor(buffer: &GapBuffer) -> (usize, usize) {
+        (buffer.line_index(), buffer.col_index())
+    }
+
+    #[test]
+    fn typing_inserts_at_the_cursor() {
+        let mut buffer = GapBuffer::new();
+        buffer.push_str("hello\nworld");
+        assert_eq!(buffer.to_string(), "hello\nworld");
+        assert_eq!(cursor(&buffer), (1, 5));
+    }
+
+    #[test]
+    fn typing_grows_the_buffer() {
+        let mut buffer = GapBuffer::new();
+        let text = "x".repeat(1000);
+        buffer.push_str(&text);
+        assert_eq!(buffer.to_string(), text);
+        assert_eq!(cursor(&buffer), (0, 1000));
+    }
+
+    #[test]
+    fn loaded_text_starts_with_the_cursor_at_the_beginning() {
+        let mut buffer = GapBuffer::from_string("abc\ndef");
+        assert_eq!(cursor(&buffer), (0, 0));
+        buffer.push('>');
+        assert_eq!(buffer.to_string(), ">abc\ndef");
+    }
+
+    #[test]
+    fn insert_in_the_middle_of_a_line() {
+        let mut buffer = GapBuffer::from_string("held");
+        buffer.move_right();
+        buffer.move_right();
+        buffer.move_right();
+        buffer.push('l');
+        buffer.push('o');
+        buffer.push(' ');
+        assert_eq!(buffer.to_string(), "hello d");
+        assert_eq!(cursor(&buffer), (0, 6));
+    }
+
+    #[test]
+    fn move_left_and_right_wrap_between_lines() {
+        let mut buffer = GapBuffer::from_string("ab\ncd");
+        buffer.move_right();
+        buffer.move_right();
+        assert_eq!(cursor(&buffer), (0, 2));
+        buffer.move_right();
+        assert_eq!(cursor(&buffer), (1, 0));
+        buffer.move_left();
+        assert_eq!(cursor(&buffer), (0, 2));
+        buffer.move_left();
+        buffer.move_left();
+        buffer.move_left();
+        assert_eq!(cursor(&buffer), (0, 0));
+    }
+
+    #[test]
+    fn move_right_stops_at_the_end() {
+        let mut buffer = GapBuffer::from_string("ab");
+        for _ in 0..5 {
+            buffer.move_right();
+        }
+        assert_eq!(cursor(&buffer), (0, 2));
+        buffer.push('c');
+        assert_eq!(buffer.to_string(), "abc");
+    }
+
+    #[test]
+    fn move_up_and_down_keep_the_column_when_possible() {
+        let mut buffer = GapBuffer::from_string("long line\nab\nanother line");
+        for _ in 0..6 {
+            buffer.move_right();
+        }
+        buffer.move_down();
+        assert_eq!(cursor(&buffer), (1, 2));
+        buffer.move_down();
+        assert_eq!(cursor(&buffer), (2, 2));
+        buffer.move_down();
+        assert_eq!(cursor(&buffer), (2, 2));
+        buffer.push('X');
+        assert_eq!(buffer.to_string(), "long line\nab\nanXother line");
+        buffer.move_up();
+        buffer.move_up();
+        assert_eq!(cursor(&buffer), (0, 2));
+        buffer.move_up();
+        assert_eq!(cursor(&buffer), (0, 2));
+        buffer.push('Y');
+        assert_eq!(buffer.to_string(), "loYng line\nab\nanXother line");
+    }
+
+    #[test]
+    fn move_down_to_an_empty_last_line() {
+        let mut buffer = GapBuffer::from_string("abc\n");
+        buffer.move_right();
+        buffer.move_down();
+        assert_eq!(cursor(&buffer), (1, 0));
+        buffer.push('z');
+        assert_eq!(buffer.to_string(), "abc\nz");
+    }
+
+    #[test]
+    fn remove_deletes_the_previous_character() {
+        let mut buffer = GapBuffer::new();
+        buffer.push_str("abc");
+        buffer.remove();
+        assert_eq!(buffer.to_string(), "ab");
+        assert_eq!(cursor(&buffer), (0, 2));
+    }
+
+    #[test]
+    fn remove_at_the_beginning_of_a_line_joins_it_with_the_previous_one() {
+        let mut buffer = GapBuffer::from_string("ab\ncd");
+        buffer.move_down();
+        assert_eq!(cursor(&buffer), (1, 0));
+        buffer.remove();
+        assert_eq!(buffer.to_string(), "abcd");
+        assert_eq!(cursor(&buffer), (0, 2));
+    }
+
+    #[test]
+    fn set_position_tracks_the_line_and_column() {
+        let mut buffer = GapBuffer::from_string("ab\ncde\nf");
+        buffer.set_position(5);
+        assert_eq!(cursor(&buffer), (1, 2));
+        buffer.set_position(8);
+        assert_eq!(cursor(&buffer), (2, 1));
+        buffer.set_position(100);
+        assert_eq!(cursor(&buffer), (2, 1));
+        buffer.set_position(1);
+        assert_eq!(cursor(&buffer), (0, 1));
+    }
+
+    #[test]
+    fn delete_forward_returns_the_removed_text() {
+        let mut buffer = GapBuffer::from_string("ab\ncd");
+        buffer.move_right();
+        assert_eq!(buffer.delete_forward(3), "b\nc");
+        assert_eq!(buffer.to_string(), "ad");
+        assert_eq!(buffer.delete_forward(5), "d");
+        assert_eq!(cursor(&buffer), (0, 1));
+    }
+
+    #[test]
+    fn lines_are_read_around_the_cursor() {
+        let mut buffer = GapBuffer::from_string("zero\none\ntwo\nthree");
+        assert_eq!(buffer.line_count(), 4);
+        assert_eq!(buffer.lines(1, 2), vec!["one", "two"]);
+        buffer.set_position(11);
+        assert_eq!(buffer.lines(0, 10), vec!["zero", "one", "two", "three"]);
+        assert_eq!(buffer.lines(3, 1), vec!["three"]);
+        assert!(buffer.lines(4, 1).is_empty());
+        buffer.push('\n');
+        assert_eq!(buffer.line_count(), 5);
+        assert_eq!(buffer.lines(2, 2), vec!["tw", "o"]);
+    }
+
+    #[test]
+    fn find_searches_on_both_sides_of_the_gap() {
+        let mut buffer = GapBuffer::from_string("one two\none two");
+        buffer.set_position(6);
+        assert_eq!(buffer.find("two", 0, true), Some(4));
+        assert_eq!(buffer.find("two", 5, true), Some(12));
+        assert_eq!(buffer.find("two", 12, false), Some(4));
+        assert_eq!(buffer.find("one", 15, false), Some(8));
+        assert_eq!(buffer.find("three", 0, true), None);
+        assert_eq!(buffer.find("two", 13, true), None);
+    }
+
+    #[test]
+    fn the_cursor_moves_over_grapheme_clusters() {
+        // e and a combining acute accent, then a family emoji made of four people joined together
+        let mut buffer = GapBuffer::from_string("e\u{301}x\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{200D}\u{1F466}!");
+        buffer.move_right();
+        assert_eq!(cursor(&buffer), (0, 2));
+        buffer.move_right();
+        buffer.move_right();
+        assert_eq!(cursor(&buffer), (0, 10));
+        assert_eq!(buffer.display_col(), 4);
+        buffer.move_left();
+        assert_eq!(cursor(&buffer), (0, 3));
+        buffer.move_right();
+        assert_eq!(buffer.remove().as_deref(), Some("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{200D}\u{1F466}"));
+        assert_eq!(buffer.to_string(), "e\u{301}x!");
+    }
+
+    #[test]
+    fn move_up_and_down_keep_the_terminal_column() {
+        // Wide characters take two columns each
+        let mut buffer = GapBuffer::from_string("\u{4F60}\u{597D}\u{4E16}\u{754C}\nabcdef\na\u{754C}b");
+        buffer.move_right();
+        buffer.move_right();
+        assert_eq!(buffer.display_col(), 4);
+        buffer.move_down();
+        assert_eq!(cursor(&buffer), (1, 4));
+        buffer.move_right();
+        buffer.move_down();
+        assert_eq!(cursor(&buffer), (2, 3));
+        assert_eq!(buffer.display_col(), 4);
+        buffer.move_up();
+        assert_eq!(cursor(&buffer), (1, 4));
+        buffer.move_up();
+        assert_eq!(cursor(&buffer), (0, 2));
+    }
+
+    #[test]
+    fn long_lines_do_not_overflow_the_column() {
+        let mut buffer = GapBuffer::new();
+        buffer.push_str(&"x".repeat(70_000));
+        assert_eq!(cursor(&buffer), (0, 70_000));
+        buffer.push('\n');
+        buffer.move_up();
+        assert_eq!(cursor(&buffer), (0, 0));
+    }
+
+    #[test]
+    fn remove_at_the_beginning_does_nothing() {
+        let mut buffer = GapBuffer::from_string("ab");
+        buffer.remove();
+        assert_eq!(buffer.to_string(), "ab");
+        assert_eq!(cursor(&buffer), (0, 0));
+    }
+}
@@ -0,0 +1,275 @@
+use crossterm::style::Color;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Style {
+    Plain,
+    Keyword,
+    Type,
+    Key,
+    String,
+    Number,
+    Comment,
+    Heading,
+}
+
+impl Style {
+    pub fn color(self) -> Option<Color> {
+        match self {
+            Style::Plain => None,
+            Style::Keyword => Some(Color::Magenta),
+            Style::Type => Some(Color::Cyan),
+            Style::Key => Some(Color::Blue),
+            Style::String => Some(Color::Green),
+            Style::Number => Some(Color::Yellow),
+            Style::Comment => Some(Color::DarkGrey),
+            Style::Heading => Some(Color::Red),
+        }
+    }
+}
+
+pub trait Highlighter {
+    // Style of every character of the line. Lines are highlighted on their own, so that only the ones on
+    // screen are looked at when the editor redraws
+    fn highlight(&self, line: &str) -> Vec<Style>;
+}
+
+struct Language {
+    // Highlighting rules of a programming or configuration language
+    keywords: &'static [&'static str],
+    line_comment: Option<&'static str>,
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    key_separator: Option<char>, // a word or string followed by it is a key, as in JSON and TOML
+    sections: bool,               // lines like [section] are headings, as in TOML
+    variables: bool,              // $NAME and ${NAME} are variables, as in shell scripts
+    types: bool,                  // capitalized words are types, as in Rust
+}
+
+const RUST: Language = Language {
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false", "fn", "for", "if", "impl", "in",
+        "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true",
+        "type", "unsafe", "use", "where", "while",
+    ],
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"'],
+    key_separator: None,
+    sections: false,
+    variables: false,
+    types: true,
+};
+
+const JSON: Language = Language {
+    keywords: &["true", "false", "null"],
+    line_comment: None,
+    block_comment: None,
+    quotes: &['"'],
+    key_separator: Some(':'),
+    sections: false,
+    variables: false,
+    types: false,
+};
+
+const TOML: Language = Language {
+    keywords: &["true", "false"],
+    line_comment: Some("#"),
+    block_comment: None,
+    quotes: &['"', '\''],
+    key_separator: Some('='),
+    sections: true,
+    variables: false,
+    types: false,
+};
+
+const SHELL: Language = Language {
+    keywords: &[
+        "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case", "esac", "in", "function", "return", "export", "local",
+        "echo", "exit",
+    ],
+    line_comment: Some("#"),
+    block_comment: None,
+    quotes: &['"', '\''],
+    key_separator: None,
+    sections: false,
+    variables: true,
+    types: false,
+};
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn starts_with(chars: &[char], index: usize, prefix: &str) -> bool {
+    prefix.chars().enumerate().all(|(offset, c)| chars.get(index + offset) == Some(&c))
+}
+
+impl Language {
+    fn is_key(&self, chars: &[char], end: usize) -> bool {
+        let Some(separator) = self.key_separator else { return false };
+        chars[end..].iter().find(|c| !c.is_whitespace()) == Some(&separator)
+    }
+}
+
+impl Highlighter for Language {
+    fn highlight(&self, line: &str) -> Vec<Style> {
+        let chars: Vec<char> = line.chars().collect();
+        let mut styles = vec![Style::Plain; chars.len()];
+        if self.sections && line.trim_start().starts_with('[') {
+            styles.fill(Style::Heading);
+            return styles;
+        }
+        let mut index = 0;
+        while index < chars.len() {
+            let c = chars[index];
+            let after_blank = index == 0 || chars[index - 1].is_whitespace();
+            let (end, style) = if self.line_comment.is_some_and(|comment| starts_with(&chars, index, comment) && (comment != "#" || after_blank)) {
+                (chars.len(), Style::Comment)
+            } else if let Some((start, end)) = self.block_comment.filter(|(start, _)| starts_with(&chars, index, start)) {
+                let from = index + start.chars().count();
+                let close = (from..chars.len()).find(|offset| starts_with(&chars, *offset, end));
+                (close.map_or(chars.len(), |close| close + end.chars().count()), Style::Comment)
+            } else if self.quotes.contains(&c) {
+                // A backslash escapes the next character, a string left open goes to the end of the line
+                let mut end = index + 1;
+                while end < chars.len() && chars[end] != c {
+                    end += if chars[end] == '\\' { 2 } else { 1 };
+                }
+                let end = (end + 1).min(chars.len());
+                (end, if self.is_key(&chars, end) { Style::Key } else { Style::String })
+            } else if self.variables && c == '$' {
+                let end = if chars.get(index + 1) == Some(&'{') {
+                    (index..chars.len()).find(|offset| chars[*offset] == '}').map_or(chars.len(), |close| close + 1)
+                } else {
+                    (index + 1..chars.len()).find(|offset| !is_word(chars[*offset])).unwrap_or(chars.len())
+                };
+                (end, Style::Key)
+            } else if c.is_ascii_digit() && (index == 0 || !is_word(chars[index - 1])) {
+                let end = (index..chars.len()).find(|offset| !(is_word(chars[*offset]) || chars[*offset] == '.')).unwrap_or(chars.len());
+                (end, Style::Number)
+            } else if is_word(c) || (self.key_separator.is_some() && c == '-') {
+                let end = (index..chars.len()).find(|offset| !(is_word(chars[*offset]) || chars[*offset] == '-')).unwrap_or(chars.len());
+                let word: String = chars[index..end].iter().collect();
+                let style = if self.is_key(&chars, end) {
+                    Style::Key
+                } else if self.keywords.contains(&word.as_str()) {
+                    Style::Keyword
+                } else if self.types && c.is_uppercase() {
+                    Style::Type
+                } else {
+                    Style::Plain
+                };
+                (end, style)
+            } else {
+                (index + 1, Style::Plain)
+            };
+            styles[index..end].fill(style);
+            index = end;
+        }
+        styles
+    }
+}
+
+struct Markdown;
+
+impl Highlighter for Markdown {
+    fn highlight(&self, line: &str) -> Vec<Style> {
+        // Headings, fences and quotes color the whole line, list markers and `code` spans only themselves
+        let chars: Vec<char> = line.chars().collect();
+        let mut styles = vec![Style::Plain; chars.len()];
+        let trimmed = line.trim_start();
+        let indent = chars.len() - trimmed.chars().count();
+        if trimmed.starts_with('#') {
+            styles.fill(Style::Heading);
+            return styles;
+        }
+        if trimmed.starts_with("```") || trimmed.starts_with('>') {
+            styles.fill(Style::Comment);
+            return styles;
+        }
+        let is_list = ["- ", "* ", "+ "].iter().any(|marker| trimmed.starts_with(marker))
+            || trimmed.find(". ").is_some_and(|dot| dot > 0 && trimmed[..dot].chars().all(|c| c.is_ascii_digit()));
+        if is_list {
+            let marker = trimmed.chars().take_while(|c| !c.is_whitespace()).count();
+            styles[indent..indent + marker].fill(Style::Keyword);
+        }
+        let mut index = indent;
+        while index < chars.len() {
+            if chars[index] == '`' {
+                if let Some(close) = (index + 1..chars.len()).find(|offset| chars[*offset] == '`') {
+                    styles[index..=close].fill(Style::String);
+                    index = close;
+                }
+            }
+            index += 1;
+        }
+        styles
+    }
+}
+
+pub fn for_file(name: &str) -> Option<Box<dyn Highlighter>> {
+    // Highlighter of the language of the file, from the extension of its name
+    let name = name.rsplit('/').next().unwrap_or(name);
+    let (_, extension) = name.rsplit_once('.')?;
+    match extension.to_ascii_lowercase().as_str() {
+        "rs" => Some(Box::new(RUST)),
+        "json" => Some(Box::new(JSON)),
+        "toml" => Some(Box::new(TOML)),
+        "sh" | "bash" => Some(Box::new(SHELL)),
+        "md" | "markdown" => Some(Box::new(Markdown)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{for_file, Style};
+
+    fn styles(name: &str, line: &str) -> String {
+        // One letter per character: k(eyword), t(ype), K(ey), s(tring), n(umber), c(omment), h(eading), or a dot
+        let highlighter = for_file(name).unwrap();
+        highlighter
+            .highlight(line)
+            .iter()
+            .map(|style| match style {
+                Style::Plain => '.',
+                Style::Keyword => 'k',
+                Style::Type => 't',
+                Style::Key => 'K',
+                Style::String => 's',
+                Style::Number => 'n',
+                Style::Comment => 'c',
+                Style::Heading => 'h',
+            })
+            .collect()
+    }
+
+    #[test]
+    fn languages_are_found_from_the_extension() {
+        assert!(for_file("/src/main.rs").is_some());
+        assert!(for_file("Cargo.TOML").is_some());
+        assert!(for_file("notes.txt").is_none());
+        assert!(for_file("Makefile").is_none());
+    }
+
+    #[test]
+    fn rust_lines_are_highlighted() {
+        assert_eq!(styles("a.rs", "let x: Vec = \"a\\\"b\"; // done"), "kkk....ttt...ssssss..ccccccc");
+        assert_eq!(styles("a.rs", "f(1.5) /* x */ y2"), "..nnn..ccccccc...");
+    }
+
+    #[test]
+    fn keys_and_sections_are_highlighted() {
+        assert_eq!(styles("a.json", "{\"key\": true, \"v\": \"s\"}"), ".KKKKK..kkkk..KKK..sss.");
+        assert_eq!(styles("a.toml", "[package]"), "hhhhhhhhh");
+        assert_eq!(styles("a.toml", "edition = \"2021\" # x"), "KKKKKKK...ssssss.ccc");
+        assert_eq!(styles("a.sh", "echo $HOME#x # y"), "kkkk.KKKKK...ccc");
+    }
+
+    #[test]
+    fn markdown_lines_are_highlighted() {
+        assert_eq!(styles("a.md", "# Title"), "hhhhhhh");
+        assert_eq!(styles("a.md", "- run `cargo`"), "k.....sssssss");
+        assert_eq!(styles("a.md", "12. x"), "kkk..");
+    }
+}
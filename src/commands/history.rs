@@ -0,0 +1,223 @@
+use std::collections::VecDeque;
+use super::gap_buffer::GapBuffer;
+
+const MAX_HISTORY_SIZE: usize = 1 << 20; // characters kept for undo and redo
+
+enum Change {
+    Insert { position: usize, text: String },
+    Delete { position: usize, text: String },
+}
+
+impl Change {
+    fn size(&self) -> usize {
+        match self {
+            Change::Insert { text, .. } | Change::Delete { text, .. } => text.len(),
+        }
+    }
+
+    fn apply(&self, data: &mut GapBuffer) {
+        match self {
+            Change::Insert { position, text } => {
+                data.set_position(*position);
+                data.push_str(text);
+            },
+            Change::Delete { position, text } => {
+                data.set_position(*position);
+                data.delete_forward(text.chars().count());
+            },
+        }
+    }
+
+    fn revert(&self, data: &mut GapBuffer) {
+        match self {
+            Change::Insert { position, text } => {
+                data.set_position(*position);
+                data.delete_forward(text.chars().count());
+            },
+            Change::Delete { position, text } => {
+                data.set_position(*position);
+                data.push_str(text);
+            },
+        }
+    }
+}
+
+struct Group {
+    // Changes undone and redone together, with the cursor to go back to on undo
+    changes: Vec<Change>,
+    cursor_before: usize,
+    cursor_after: usize,
+}
+
+impl Group {
+    fn size(&self) -> usize {
+        self.changes.iter().map(Change::size).sum()
+    }
+}
+
+pub struct History {
+    undo: VecDeque<Group>,
+    redo: Vec<Group>,
+    size: usize,
+    grouping: bool, // whether the next change joins the last group
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            size: 0,
+            grouping: false,
+        }
+    }
+
+    pub fn break_group(&mut self) {
+        // Moving the cursor, or changing mode, ends the group of changes being typed
+        self.grouping = false;
+    }
+
+    fn push(&mut self, change: Change, cursor_before: usize, cursor_after: usize) {
+        // A new change makes the undone ones unreachable. Consecutive characters typed or erased
+        // are merged into one change, and the oldest groups are dropped beyond the memory budget
+        self.size -= self.redo.drain(..).map(|group| group.size()).sum::<usize>();
+        self.size += change.size();
+        let group = match self.undo.back_mut() {
+            Some(group) if self.grouping => group,
+            _ => {
+                self.undo.push_back(Group {
+                    changes: Vec::new(),
+                    cursor_before,
+                    cursor_after,
+                });
+                self.undo.back_mut().unwrap()
+            },
+        };
+        group.cursor_after = cursor_after;
+        match (group.changes.last_mut(), change) {
+            (Some(Change::Insert { position, text }), Change::Insert { position: new_position, text: new_text })
+                if *position + text.chars().count() == new_position =>
+            {
+                text.push_str(&new_text);
+            },
+            (Some(Change::Delete { position, text }), Change::Delete { position: new_position, text: new_text })
+                if new_position + new_text.chars().count() == *position =>
+            {
+                text.insert_str(0, &new_text);
+                *position = new_position;
+            },
+            (_, change) => group.changes.push(change),
+        }
+        self.grouping = true;
+        while self.size > MAX_HISTORY_SIZE {
+            match self.undo.pop_front() {
+                Some(group) => self.size -= group.size(),
+                None => break,
+            }
+        }
+    }
+
+    pub fn record_insert(&mut self, position: usize, text: &str) {
+        self.push(Change::Insert { position, text: text.to_string() }, position, position + text.chars().count());
+    }
+
+    pub fn record_delete(&mut self, position: usize, text: &str, cursor_before: usize) {
+        self.push(Change::Delete { position, text: text.to_string() }, cursor_before, position);
+    }
+
+    pub fn undo(&mut self, data: &mut GapBuffer) -> bool {
+        // Revert the last group of changes, returning false when there is nothing to undo
+        self.grouping = false;
+        let Some(group) = self.undo.pop_back() else { return false };
+        for change in group.changes.iter().rev() {
+            change.revert(data);
+        }
+        data.set_position(group.cursor_before);
+        self.redo.push(group);
+        true
+    }
+
+    pub fn redo(&mut self, data: &mut GapBuffer) -> bool {
+        // Apply again the last undone group of changes, returning false when there is nothing to redo
+        self.grouping = false;
+        let Some(group) = self.redo.pop() else { return false };
+        for change in &group.changes {
+            change.apply(data);
+        }
+        data.set_position(group.cursor_after);
+        self.undo.push_back(group);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::History;
+    use crate::commands::gap_buffer::GapBuffer;
+
+    fn type_text(data: &mut GapBuffer, history: &mut History, text: &str) {
+        for c in text.chars() {
+            history.record_insert(data.position(), &c.to_string());
+            data.push(c);
+        }
+    }
+
+    fn backspace(data: &mut GapBuffer, history: &mut History) {
+        let cursor_before = data.position();
+        if let Some(c) = data.remove() {
+            history.record_delete(data.position(), &c.to_string(), cursor_before);
+        }
+    }
+
+    #[test]
+    fn typing_is_undone_as_one_group() {
+        let mut data = GapBuffer::new();
+        let mut history = History::new();
+        type_text(&mut data, &mut history, "hello");
+        history.break_group();
+        type_text(&mut data, &mut history, " world");
+        assert!(history.undo(&mut data));
+        assert_eq!(data.to_string(), "hello");
+        assert_eq!(data.position(), 5);
+        assert!(history.undo(&mut data));
+        assert_eq!(data.to_string(), "");
+        assert!(!history.undo(&mut data));
+    }
+
+    #[test]
+    fn redo_applies_the_undone_changes() {
+        let mut data = GapBuffer::new();
+        let mut history = History::new();
+        type_text(&mut data, &mut history, "ab\ncd");
+        history.undo(&mut data);
+        assert!(history.redo(&mut data));
+        assert_eq!(data.to_string(), "ab\ncd");
+        assert_eq!((data.line_index(), data.col_index()), (1, 2));
+        assert!(!history.redo(&mut data));
+    }
+
+    #[test]
+    fn backspaces_are_undone_together() {
+        let mut data = GapBuffer::from_string("abc\ndef");
+        let mut history = History::new();
+        data.set_position(5);
+        backspace(&mut data, &mut history);
+        backspace(&mut data, &mut history);
+        backspace(&mut data, &mut history);
+        assert_eq!(data.to_string(), "abef");
+        history.undo(&mut data);
+        assert_eq!(data.to_string(), "abc\ndef");
+        assert_eq!(data.position(), 5);
+    }
+
+    #[test]
+    fn a_new_change_drops_the_redo_history() {
+        let mut data = GapBuffer::new();
+        let mut history = History::new();
+        type_text(&mut data, &mut history, "one");
+        history.undo(&mut data);
+        type_text(&mut data, &mut history, "two");
+        assert!(!history.redo(&mut data));
+        assert_eq!(data.to_string(), "two");
+    }
+}
@@ -1,6 +1,28 @@
-use crate::types::Inode;
+mod acl;
+mod audit;
+mod encryption;
+mod gap_buffer;
+mod highlight;
+mod history;
 mod new_file;
+mod permissions;
+mod quota;
+#[cfg(test)]
+mod screen;
+mod search;
+mod terminal;
+mod users;
+mod vi;
 
-pub fn create_new_file(name: String, parent_inode: &mut Inode) -> Result<(), &'static str> {
-    new_file::create_new_file(name, parent_inode)
-}
\ No newline at end of file
+pub use acl::{handle_getfacl, handle_setfacl};
+pub use audit::handle_audit;
+pub use encryption::{handle_decrypt, handle_encrypt};
+pub use new_file::Files;
+pub use permissions::{handle_chgrp, handle_chmod, handle_chown, handle_immutable, handle_readonly};
+pub use quota::{handle_quota, handle_setquota};
+pub use users::{handle_login, handle_logout, handle_passwd, handle_useradd, handle_userdel, handle_whoami};
+pub use vi::Registers;
+
+pub fn edit_file(name: &str, data: &str, files: &mut dyn Files, registers: &mut Registers) -> Result<(), String> {
+    terminal::edit_file(name, data, files, registers)
+}
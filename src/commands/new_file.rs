@@ -1,292 +1,1048 @@
-use std::io::{stdout, Stdout, Write};
-use std::mem;
-use std::thread::sleep;
-use std::time::Duration;
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, poll, read};
-use crossterm::{QueueableCommand, terminal};
-use crossterm::cursor::{MoveTo};
-use crossterm::terminal::{Clear, ClearType, disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
-use crate::types::{Inode};
-
-struct GapBuffer {
-    data: Vec<Vec<char>>,
-    line_index: u16,
-    col_index: u16,
-}
-
-impl GapBuffer{
-    fn new() -> GapBuffer {
-        GapBuffer {
-            data: vec![vec![]],
-            line_index: 0,
-            col_index: 0,
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::Color;
+use unicode_segmentation::UnicodeSegmentation;
+use super::gap_buffer::{self, GapBuffer};
+use super::highlight::{self, Highlighter, Style};
+use super::history::History;
+use super::search::{self, Substitution};
+use super::vi::{self, Command, Operator, Parse, Registers};
+use crate::utils;
+
+pub trait Files {
+    // Access of the editor to the files it opens and saves
+    fn read(&mut self, path: &str) -> Result<Option<String>, String>; // None when the file doesn't exist yet
+    fn write(&mut self, path: &str, data: &str) -> Result<(), String>;
+}
+
+pub trait EventSource {
+    // Events received since the last call, waiting for at least one. None when there won't be any more
+    fn read(&mut self) -> Option<Vec<Event>>;
+}
+
+pub trait Renderer {
+    // Where the editor draws itself: a grid of size (columns, rows) written from the position of the cursor
+    fn size(&self) -> (usize, usize);
+    fn clear(&mut self);
+    fn move_to(&mut self, col: usize, row: usize);
+    fn write(&mut self, text: &str);
+    fn set_color(&mut self, color: Option<Color>); // None for the default color
+    fn set_reverse(&mut self, reverse: bool);
+    fn flush(&mut self); // the drawing is complete, with the cursor where it was moved last
+}
+
+const MAX_WINDOWS: usize = 2;
+
+struct Buffer {
+    // A file open in the editor, with its text and history. Its cursor is the one of the window editing it
+    name: String,
+    data: GapBuffer,
+    history: History,
+    modified: bool,
+    highlighter: Option<Box<dyn Highlighter>>, // syntax highlighting of the language of the file, if known
+}
+
+impl Buffer {
+    fn new(name: &str, initial_data: &str) -> Buffer {
+        Buffer {
+            name: name.to_string(),
+            data: if initial_data.is_empty() {
+                GapBuffer::new()
+            } else {
+                GapBuffer::from_string(initial_data)
+            },
+            history: History::new(),
+            modified: false,
+            highlighter: highlight::for_file(name),
         }
     }
+}
 
-    fn clone(&self) -> GapBuffer {
-        GapBuffer {
-            data: self.data.clone(),
-            line_index: self.line_index,
-            col_index: self.col_index,
+#[derive(Clone, Copy)]
+struct Window {
+    // A view on a buffer. The window with the focus uses the cursor of its buffer, the others keep theirs here
+    buffer: usize,
+    cursor: usize,
+    top_line: usize,
+    left_col: usize,
+}
+
+impl Window {
+    fn scroll(&mut self, data: &GapBuffer, rows: usize, cols: usize) {
+        // Move the viewport the least needed to keep the cursor in the text area of rows by cols
+        // The columns are those of the terminal, wide characters taking two of them
+        let line = data.line_index();
+        let col = data.display_col();
+        if line < self.top_line {
+            self.top_line = line;
+        } else if line >= self.top_line + rows {
+            self.top_line = line + 1 - rows;
+        }
+        if col < self.left_col {
+            self.left_col = col;
+        } else if col >= self.left_col + cols {
+            self.left_col = col + 1 - cols;
         }
     }
+}
 
-    fn to_string(&self) -> String {
-        let mut result = String::new();
-        let mut idx_line: u16 = 1;
-        for line in &self.data {
-            for c in line {
-                result.push(*c);
-            }
-            if idx_line < self.data.len() as u16 {
-                result.push('\n');
-            }
-            idx_line += 1;
+struct Editor<'a> {
+    // State of the editor: the open files, the windows showing them and the mode.
+    // Files are read and saved through the files given to the editor
+    files: &'a mut dyn Files,
+    buffers: Vec<Buffer>,
+    windows: Vec<Window>,
+    window: usize,  // window with the focus
+    vertical: bool, // whether the windows are side by side instead of one above the other
+    input_mode: bool,
+    quit: bool,
+    confirm_quit: bool, // quitting with unsaved changes waits for confirmation
+    prompt: Option<String>, // search or command being typed on the last line, with its leading character
+    message: Option<String>,
+    last_search: Option<(String, bool)>, // pattern and direction repeated by n and N
+    substitution: Option<Substitution>, // substitute command waiting for confirmation
+    pending: String, // keys of a vi command typed so far, like a count or an operator waiting for its motion
+    registers: &'a mut Registers,
+    selection: Option<(usize, bool)>, // anchor of the selection in visual mode, and whether it selects whole lines
+}
+
+impl<'a> Editor<'a> {
+    fn new(name: &str, initial_data: &str, files: &'a mut dyn Files, registers: &'a mut Registers) -> Editor<'a> {
+        Editor {
+            files,
+            buffers: vec![Buffer::new(name, initial_data)],
+            windows: vec![Window { buffer: 0, cursor: 0, top_line: 0, left_col: 0 }],
+            window: 0,
+            vertical: false,
+            input_mode: false,
+            quit: false,
+            confirm_quit: false,
+            prompt: None,
+            message: None,
+            last_search: None,
+            substitution: None,
+            pending: String::new(),
+            registers,
+            selection: None,
         }
-        result
     }
 
-    fn push(&mut self, c: char) {
-        self.data[self.line_index as usize].insert(self.col_index as usize, c);
-        self.col_index += 1;
+    fn current(&self) -> usize {
+        self.windows[self.window].buffer
     }
 
-    fn push_line(&mut self) {
-        self.data.insert(self.line_index as usize + 1, vec![]);
-        self.line_index += 1;
-        self.col_index = 0;
+    fn buffer(&mut self) -> &mut Buffer {
+        let current = self.current();
+        &mut self.buffers[current]
     }
 
+    fn focus(&mut self, window: usize) {
+        // Give the focus to the window, which takes its cursor back from its buffer
+        let cursor = self.buffer().data.position();
+        self.windows[self.window].cursor = cursor;
+        self.window = window;
+        let cursor = self.windows[window].cursor;
+        self.buffer().data.set_position(cursor);
+        self.selection = None;
+        self.pending.clear();
+    }
+
+    fn show(&mut self, buffer: usize) {
+        // Show the buffer in the window with the focus, where it was last edited
+        self.windows[self.window] = Window { buffer, cursor: 0, top_line: 0, left_col: 0 };
+        self.selection = None;
+        self.pending.clear();
+    }
 
-    fn remove(&mut self) {
-        if self.col_index > 0 {
-            self.col_index -= 1;
-            self.data[self.line_index as usize].remove(self.col_index as usize);
-        } else if self.line_index > 0 {
-            self.line_index -= 1;
-            self.data.remove(self.line_index as usize);
+    fn open(&mut self, path: &str) -> Result<usize, String> {
+        // Buffer of the file, read if it isn't open yet. A file that doesn't exist is created when saved
+        let components = utils::normalize_path(path);
+        if let Some(index) = self.buffers.iter().position(|buffer| utils::normalize_path(&buffer.name) == components) {
+            return Ok(index);
         }
+        let data = self.files.read(path)?;
+        self.message = Some(format!("\"{}\"{}", path, if data.is_none() { " [New]" } else { "" }));
+        self.buffers.push(Buffer::new(path, &data.unwrap_or_default()));
+        Ok(self.buffers.len() - 1)
     }
+}
 
-    fn move_left(&mut self) {
-        // This function will move the cursor to the left
-        // If it is in the first column, it will move to the previous line
-        if self.col_index == 0 {
-            // Move to Previous Line
-            if self.line_index > 0 {
-                self.line_index -= 1;
-                self.col_index = (self.data[self.line_index as usize].len()) as u16;
-            }
-        } else if self.col_index > 1 {
-            // Move to the previous character
-            self.col_index -= 1;
+fn write_line(renderer: &mut dyn Renderer, line: &str, left_col: usize, cols: usize, pattern: Option<&str>, selected: (usize, usize), styles: &[Style]) {
+    // Write the terminal columns of the line from left_col, colored by their syntax styles, with the matches of the last search
+    // and the selected characters in reverse video. Control characters like tabs, and wide characters cut by an edge, are drawn as spaces
+    let highlighted = search::highlights(line, pattern.unwrap_or(""));
+    let mut reversed = false;
+    let mut color = None;
+    let mut col = 0;
+    let mut index = 0;
+    for grapheme in line.graphemes(true) {
+        let (start, end) = (col, col + gap_buffer::grapheme_width(grapheme));
+        let first_char = index;
+        col = end;
+        index += grapheme.chars().count();
+        if end <= left_col {
+            continue;
+        }
+        if start >= left_col + cols {
+            break;
+        }
+        let highlight = highlighted[first_char] || (selected.0..selected.1).contains(&first_char);
+        if highlight != reversed {
+            reversed = highlight;
+            renderer.set_reverse(reversed);
+        }
+        let style_color = styles.get(first_char).and_then(|style| style.color());
+        if style_color != color {
+            color = style_color;
+            renderer.set_color(color);
+        }
+        if start < left_col || end > left_col + cols || grapheme.chars().any(char::is_control) {
+            let visible = end.min(left_col + cols) - start.max(left_col);
+            renderer.write(&" ".repeat(visible));
         } else {
-            // Move to the beginning of the line
-            self.col_index = 0;
+            renderer.write(grapheme);
         }
     }
+    if reversed {
+        renderer.set_reverse(false);
+    }
+    if color.is_some() {
+        renderer.set_color(None);
+    }
+}
+
+fn render_window(renderer: &mut dyn Renderer, editor: &mut Editor, index: usize, area: (usize, usize, usize, usize)) -> Option<(usize, usize)> {
+    // Draw the window in the area given by its row, column, height and width: the visible part of its text with line numbers,
+    // then its status line. Returns where the cursor is on screen for the window with the focus, the cursor of its buffer
+    // being elsewhere for the others
+    let (top, left, height, width) = area;
+    let focused = index == editor.window;
+    let buffer_index = editor.windows[index].buffer;
+    let rows = height.saturating_sub(1).max(1);
+    let gutter = editor.buffers[buffer_index].data.line_count().to_string().len() + 1;
+    let cols = width.saturating_sub(gutter).max(1);
+
+    // Lines and columns of the ends of the selection, the anchor being found by moving the cursor there and back
+    let mut selection = None;
+    if focused {
+        let data = &mut editor.buffers[buffer_index].data;
+        editor.windows[index].scroll(data, rows, cols);
+        let cursor = (data.line_index(), data.col_index());
+        selection = editor.selection.map(|(anchor, linewise)| {
+            let position = data.position();
+            data.set_position(anchor);
+            let anchor = (data.line_index(), data.col_index());
+            data.set_position(position);
+            (anchor.min(cursor), anchor.max(cursor), linewise)
+        });
+    }
+
+    let window = editor.windows[index];
+    let buffer = &editor.buffers[buffer_index];
+    for (row, line) in buffer.data.lines(window.top_line, rows).iter().enumerate() {
+        let line_index = window.top_line + row;
+        let selected = match selection {
+            Some((first, last, linewise)) if (first.0..=last.0).contains(&line_index) => {
+                if linewise {
+                    (0, usize::MAX)
+                } else {
+                    let start = if line_index == first.0 { first.1 } else { 0 };
+                    let end = if line_index == last.0 { last.1 + 1 } else { usize::MAX };
+                    (start, end)
+                }
+            },
+            _ => (0, 0),
+        };
+        renderer.move_to(left, top + row);
+        renderer.write(&format!("{:>width$} ", line_index + 1, width = gutter - 1));
+        let styles = buffer.highlighter.as_ref().map(|highlighter| highlighter.highlight(line)).unwrap_or_default();
+        let pattern = editor.last_search.as_ref().map(|(pattern, _)| pattern.as_str());
+        write_line(renderer, line, window.left_col, cols, pattern, selected, &styles);
+    }
+
+    // Only the window with the focus shows the position of the cursor and the mode
+    let modified = if buffer.modified { " [+]" } else { "" };
+    let position = if focused {
+        let mode = match editor.selection {
+            Some((_, true)) => "VISUAL LINE",
+            Some((_, false)) => "VISUAL",
+            None if editor.input_mode => "INSERT",
+            None => "COMMAND",
+        };
+        format!("{} Ln {}, Col {}  {} ", editor.pending, buffer.data.line_index() + 1, buffer.data.display_col() + 1, mode)
+    } else {
+        String::new()
+    };
+    // The name keeps at least half of a window too narrow for both
+    let name: String = format!(" {}{}", buffer.name, modified).chars().take(width.saturating_sub(position.len()).max(width / 2)).collect();
+    let padding = width.saturating_sub(name.chars().count() + position.len());
+    let status: String = format!("{}{}{}", name, "─".repeat(padding), position).chars().take(width).collect();
+    renderer.move_to(left, top + rows);
+    renderer.write(&status);
+
+    if !focused {
+        return None;
+    }
+    let cursor_row = top + buffer.data.line_index() - window.top_line;
+    let cursor_col = gutter + buffer.data.display_col() - window.left_col;
+    Some((left + cursor_col.min(width.saturating_sub(1)), cursor_row))
+}
+
+fn render(renderer: &mut dyn Renderer, editor: &mut Editor) {
+    // Draw the windows, one above the other or side by side with a line between them, then the help line below them
+    let (w, h) = renderer.size();
+    let height = h.saturating_sub(1).max(2);
+    let areas = match (editor.windows.len(), editor.vertical) {
+        (1, _) => vec![(0, 0, height, w)],
+        (_, false) => vec![(0, 0, height / 2, w), (height / 2, 0, height - height / 2, w)],
+        (_, true) => {
+            let left = w.saturating_sub(1) / 2;
+            vec![(0, 0, height, left), (0, left + 1, height, w.saturating_sub(left + 1))]
+        },
+    };
 
-    fn move_right(&mut self) {
-        // This function will move the cursor to the right
-        // If it is in the last column, it will move to the next line
-        if self.col_index == self.data[self.line_index as usize].len() as u16 {
-            // Move to Next Line
-            if self.line_index + 1 < self.data.len() as u16 {
-                self.line_index += 1;
-                self.col_index = 0;
+    renderer.clear();
+    let mut cursor = (0, 0);
+    for (index, area) in areas.into_iter().enumerate() {
+        if let Some(position) = render_window(renderer, editor, index, area) {
+            cursor = position;
+        }
+        if area.1 > 0 {
+            for row in 0..height {
+                renderer.move_to(area.1 - 1, row);
+                renderer.write("│");
             }
-        } else if self.col_index + 1 < self.data[self.line_index as usize].len() as u16 {
-            // Move to the next character
-            self.col_index += 1;
-        } else {
-            // Move to the front of the last character in the line
-            self.col_index = self.data[self.line_index as usize].len() as u16;
         }
     }
 
-    fn move_up(&mut self) {
-        // This function will move the cursor up if it isn't in the first line
-        if self.line_index > 0 {
-            if self.data[(self.line_index - 1) as usize].len() > self.col_index as usize {
-                self.line_index -= 1;
-            } else {
-                self.line_index -= 1;
-                self.col_index = self.data[self.line_index as usize].len() as u16;
+    renderer.move_to(0, h.saturating_sub(1));
+    let help = if editor.input_mode {
+        "Esc: Command mode | Ctrl+Z: Undo | Ctrl+R: Redo"
+    } else if editor.selection.is_some() {
+        "Y: Yank | D: Cut | C: Change | P: Paste | \"x: Use register x | Esc: Cancel"
+    } else {
+        "S: Save | Q: Quit | I: Insert | U: Undo | /: Search | :s: Replace | :w: Save as"
+    };
+    let last_line = editor.prompt.as_deref().or(editor.message.as_deref()).unwrap_or(help);
+    let last_line: String = last_line.chars().take(w).collect();
+    renderer.write(&last_line);
+
+    if editor.prompt.is_some() {
+        renderer.move_to(last_line.chars().count().min(w.saturating_sub(1)), h.saturating_sub(1));
+    } else {
+        renderer.move_to(cursor.0, cursor.1);
+    }
+    renderer.flush();
+}
+
+fn insert(data: &mut GapBuffer, history: &mut History, c: char) {
+    history.record_insert(data.position(), &c.to_string());
+    data.push(c);
+}
+
+fn backspace(data: &mut GapBuffer, history: &mut History) {
+    let cursor_before = data.position();
+    if let Some(c) = data.remove() {
+        history.record_delete(data.position(), &c.to_string(), cursor_before);
+    }
+}
+
+fn search(editor: &mut Editor, forward: bool) {
+    // Move the cursor to the next match of the last search in the direction
+    let Some((pattern, _)) = editor.last_search.clone() else {
+        editor.message = Some(String::from("No previous search"));
+        return;
+    };
+    let data = &mut editor.buffer().data;
+    let position = data.position();
+    let from = if forward { position + 1 } else { position };
+    match search::find(data, &pattern, from, forward) {
+        Some((position, wrapped)) => {
+            data.set_position(position);
+            if wrapped {
+                editor.message = Some(String::from(if forward { "Search hit the bottom, continuing at the top" } else { "Search hit the top, continuing at the bottom" }));
             }
+        },
+        None => editor.message = Some(format!("Pattern not found: {}", pattern)),
+    }
+}
+
+fn next_confirmation(editor: &mut Editor) {
+    // Move to the next match of the substitution waiting for confirmation, or end it when there is none left
+    let current = editor.current();
+    let Some(substitution) = &editor.substitution else { return };
+    let data = &mut editor.buffers[current].data;
+    match substitution.next_match(data) {
+        Some(position) => {
+            data.set_position(position);
+            editor.message = Some(format!("Replace with {} (y/n/a/q)?", substitution.replacement));
+        },
+        None => finish_substitution(editor),
+    }
+}
+
+fn finish_substitution(editor: &mut Editor) {
+    let Some(substitution) = editor.substitution.take() else { return };
+    editor.message = Some(match substitution.count {
+        0 if substitution.confirm => String::from("No substitutions"),
+        0 => format!("Pattern not found: {}", substitution.pattern),
+        1 => String::from("1 substitution"),
+        count => format!("{} substitutions", count),
+    });
+    let buffer = editor.buffer();
+    buffer.modified |= substitution.count > 0;
+    buffer.history.break_group();
+}
+
+fn save(editor: &mut Editor, name: Option<&str>) -> bool {
+    // Write the text to its file, or to another one which the buffer then edits. Errors are shown and keep the editor open
+    let current = editor.current();
+    let buffer = &mut editor.buffers[current];
+    let name = name.unwrap_or(&buffer.name).to_string();
+    match editor.files.write(&name, &buffer.data.to_string()) {
+        Ok(()) => {
+            editor.message = Some(format!("\"{}\" written", name));
+            buffer.highlighter = highlight::for_file(&name);
+            buffer.name = name;
+            buffer.modified = false;
+            true
+        },
+        Err(error) => {
+            editor.message = Some(error);
+            false
+        },
+    }
+}
+
+fn save_all(editor: &mut Editor) -> bool {
+    // Write every buffer with unsaved changes, stopping at the first error
+    for index in 0..editor.buffers.len() {
+        let buffer = &mut editor.buffers[index];
+        if !buffer.modified {
+            continue;
+        }
+        if let Err(error) = editor.files.write(&buffer.name, &buffer.data.to_string()) {
+            editor.message = Some(format!("{} for '{}'", error, buffer.name));
+            return false;
         }
+        buffer.modified = false;
+    }
+    true
+}
+
+fn unsaved(editor: &Editor) -> Option<String> {
+    // Name of a buffer with unsaved changes, the one with the focus first
+    let current = &editor.buffers[editor.current()];
+    let mut buffers = std::iter::once(current).chain(editor.buffers.iter());
+    buffers.find(|buffer| buffer.modified).map(|buffer| buffer.name.clone())
+}
+
+fn quit(editor: &mut Editor) {
+    // Leave without saving, after confirmation when there are unsaved changes
+    if unsaved(editor).is_some() {
+        editor.confirm_quit = true;
+        editor.message = Some(String::from("Discard the unsaved changes? (y/n)"));
+    } else {
+        editor.quit = true;
+    }
+}
+
+fn close(editor: &mut Editor, force: bool) {
+    // Close the window with the focus, leaving the editor with the last one. The buffers stay open
+    if editor.windows.len() > 1 {
+        editor.windows.remove(editor.window);
+        editor.window = editor.window.min(editor.windows.len() - 1);
+        let cursor = editor.windows[editor.window].cursor;
+        editor.buffer().data.set_position(cursor);
+        editor.selection = None;
+        return;
+    }
+    match unsaved(editor) {
+        Some(name) if !force => editor.message = Some(format!("No write since the last change to {}, add ! to discard it", name)),
+        _ => editor.quit = true,
+    }
+}
+
+fn split(editor: &mut Editor, vertical: bool, file: Option<&str>) {
+    // Show the buffer, or the file, in a new window sharing the space of the one with the focus
+    if editor.windows.len() == MAX_WINDOWS {
+        editor.message = Some(format!("At most {} windows can be shown", MAX_WINDOWS));
+        return;
+    }
+    let buffer = match file.map(|file| editor.open(file)) {
+        Some(Ok(buffer)) => buffer,
+        Some(Err(error)) => {
+            editor.message = Some(error);
+            return;
+        },
+        None => editor.current(),
+    };
+    let mut window = editor.windows[editor.window];
+    window.cursor = editor.buffer().data.position();
+    editor.windows.insert(editor.window, window);
+    editor.vertical = vertical;
+    editor.window += 1;
+    if buffer != editor.current() {
+        editor.show(buffer);
     }
+}
+
+fn list_buffers(editor: &Editor) -> String {
+    // The open buffers on one line, with % before the one with the focus and [+] after those with unsaved changes
+    let buffers = editor.buffers.iter().enumerate().map(|(index, buffer)| {
+        let current = if index == editor.current() { "%" } else { "" };
+        let modified = if buffer.modified { " [+]" } else { "" };
+        format!("{} {}{}{}", index + 1, current, buffer.name, modified)
+    });
+    buffers.collect::<Vec<String>>().join("  ")
+}
 
-    fn move_down(&mut self) {
-        // This function will move the cursor down if it isn't in the last line
-        if self.line_index + 1 < self.data.len() as u16 {
-            if self.data[(self.line_index + 1) as usize].len() > self.col_index as usize {
-                self.line_index += 1;
+fn execute_command(editor: &mut Editor, command: &str) {
+    // Run a command typed after ':'
+    let command = command.trim();
+    if command.is_empty() {
+        return;
+    }
+    let (name, argument) = command.split_once(' ').map_or((command, ""), |(name, argument)| (name, argument.trim()));
+    let file = (!argument.is_empty()).then_some(argument);
+    match name {
+        "w" => {
+            save(editor, file);
+            return;
+        },
+        "wq" | "x" => {
+            if save(editor, file) {
+                close(editor, false);
+            }
+            return;
+        },
+        "wa" => {
+            if save_all(editor) {
+                editor.message = Some(String::from("All files written"));
+            }
+            return;
+        },
+        "q" | "close" => {
+            close(editor, false);
+            return;
+        },
+        "q!" => {
+            close(editor, true);
+            return;
+        },
+        "qa" => {
+            match unsaved(editor) {
+                Some(name) => editor.message = Some(format!("No write since the last change to {}, add ! to discard it", name)),
+                None => editor.quit = true,
+            }
+            return;
+        },
+        "qa!" => {
+            editor.quit = true;
+            return;
+        },
+        "e" => {
+            match file.ok_or(String::from("Usage: e <path>")).and_then(|file| editor.open(file)) {
+                Ok(buffer) if buffer != editor.current() => editor.show(buffer),
+                Ok(_) => {},
+                Err(error) => editor.message = Some(error),
+            }
+            return;
+        },
+        "bn" | "bp" => {
+            let count = editor.buffers.len();
+            let next = if name == "bn" { editor.current() + 1 } else { editor.current() + count - 1 };
+            editor.show(next % count);
+            return;
+        },
+        "ls" => {
+            editor.message = Some(list_buffers(editor));
+            return;
+        },
+        "sp" | "vs" => {
+            split(editor, name == "vs", file);
+            return;
+        },
+        "only" => {
+            // Keep the window with the focus
+            let window = editor.windows[editor.window];
+            editor.windows = vec![window];
+            editor.window = 0;
+            return;
+        },
+        _ => {},
+    }
+    let current = editor.current();
+    let buffer = &mut editor.buffers[current];
+    match Substitution::parse(command, &buffer.data) {
+        Ok(mut substitution) => {
+            // The matches are highlighted like a search, and all the replacements are undone together
+            editor.last_search = Some((substitution.pattern.clone(), true));
+            buffer.history.break_group();
+            if !substitution.confirm {
+                substitution.run(&mut buffer.data, &mut buffer.history);
+            }
+            let confirm = substitution.confirm;
+            editor.substitution = Some(substitution);
+            if confirm {
+                next_confirmation(editor);
             } else {
-                self.line_index += 1;
-                self.col_index = self.data[self.line_index as usize].len() as u16;
+                finish_substitution(editor);
             }
-        }
+        },
+        Err(error) => editor.message = Some(error),
     }
 }
 
-fn reload_terminal_command_mode(mut terminal: &Stdout, data: &str) {
-    // This function will reload the terminal outside of input mode with the data of the GapBuffer
-    let (w, h) = terminal::size().unwrap();
-    terminal.queue(Clear(ClearType::All)).unwrap();
-    terminal.queue(MoveTo(0, 0)).unwrap();
-    terminal.write(data.as_bytes()).unwrap();
-    terminal.queue(MoveTo(0, h-2)).unwrap();
-    let bar = "─".repeat(w as usize);
-    terminal.write(bar.as_bytes()).unwrap();
-    terminal.queue(MoveTo(0, h-1)).unwrap();
-    terminal.write(b"Ctrl+S: Save | I: Insert mode").unwrap();
-    terminal.flush().unwrap();
-}
-fn reload_terminal_input_mode(mut terminal: &Stdout, data: GapBuffer) {
-    // This function will reload the terminal with the data of the GapBuffer and the cursor in the right position
-    terminal.queue(Clear(ClearType::All)).unwrap();
-    terminal.queue(MoveTo(0, 0)).unwrap();
-    terminal.write(data.to_string().as_bytes()).unwrap();
-    terminal.queue(MoveTo(data.col_index, data.line_index)).unwrap();
-    terminal.flush().unwrap();
-}
-
-fn handle_key_event(event: KeyEvent, input_mode: &mut bool, quit: &mut bool, terminal: &Stdout, mut data: GapBuffer) -> GapBuffer {
-    // Handle the key event, and return the modified data
-    if event.kind == KeyEventKind::Press {
-        if *input_mode {
-            match event.code {
-                KeyCode::Char(x) => {
-                    if x == 'c' && event.modifiers == KeyModifiers::CONTROL {
-                        *input_mode = false;
-                        *quit = false;
-                        data
-                    } else{
-                        data.push(x);
-                        reload_terminal_input_mode(&terminal, data.clone());
-                        *quit = false;
-                        data
+fn handle_prompt_key(event: KeyEvent, editor: &mut Editor) {
+    // Edit the search or command being typed on the last line, and run it on Enter
+    let Some(prompt) = &mut editor.prompt else { return };
+    match event.code {
+        KeyCode::Char('c') if event.modifiers == KeyModifiers::CONTROL => editor.prompt = None,
+        KeyCode::Char(c) => prompt.push(c),
+        KeyCode::Backspace => {
+            prompt.pop();
+            if prompt.is_empty() {
+                editor.prompt = None;
+            }
+        },
+        KeyCode::Esc => editor.prompt = None,
+        KeyCode::Enter => {
+            let prompt = editor.prompt.take().unwrap();
+            let mut chars = prompt.chars();
+            match chars.next() {
+                Some(':') => execute_command(editor, chars.as_str()),
+                Some(kind) => {
+                    // An empty pattern searches again for the last one
+                    let forward = kind == '/';
+                    if !chars.as_str().is_empty() {
+                        editor.last_search = Some((chars.as_str().to_string(), forward));
+                    } else if let Some((_, direction)) = &mut editor.last_search {
+                        *direction = forward;
                     }
+                    search(editor, forward);
                 },
-                KeyCode::Left => {
-                    data.move_left();
-                    reload_terminal_input_mode(&terminal, data.clone());
-                    *quit = false;
-                    data
-                },
-                KeyCode::Right => {
-                    data.move_right();
-                    reload_terminal_input_mode(&terminal, data.clone());
-                    *quit = false;
-                    data
-                },
-                KeyCode::Up => {
-                    data.move_up();
-                    reload_terminal_input_mode(&terminal, data.clone());
-                    *quit = false;
-                    data
-                },
-                KeyCode::Down => {
-                    data.move_down();
-                    reload_terminal_input_mode(&terminal, data.clone());
-                    *quit = false;
-                    data
-                },
-                KeyCode::Backspace => {
-                    data.remove();
-                    reload_terminal_input_mode(&terminal, data.clone());
-                    *quit = false;
-                    data
-                },
-                KeyCode::Enter => {
-                    data.push_line();
-                    reload_terminal_input_mode(&terminal, data.clone());
-                    *quit = false;
-                    data
-                },
-                KeyCode::Esc => {
-                    *input_mode = false;
-                    *quit = false;
-                    data
-                },
-                _ => data
+                None => {},
             }
-        } else {
-            match event.code {
-                KeyCode::Char(x) => {
-                    if x == 's' {
-                        *quit = true;
-                        data
-                    } else if x == 'i' {
-                        reload_terminal_input_mode(&terminal, data.clone());
-                        *input_mode = true;
-                        *quit = false;
-                        data
-                    } else {
-                        data
-                    }
+        },
+        _ => {},
+    }
+}
+
+fn handle_confirmation_key(event: KeyEvent, editor: &mut Editor) {
+    // Answer the question of a substitution with the c flag for the match under the cursor
+    let current = editor.current();
+    let Some(substitution) = &mut editor.substitution else { return };
+    let Buffer { data, history, .. } = &mut editor.buffers[current];
+    let position = data.position();
+    match event.code {
+        KeyCode::Char('y') => substitution.replace(position, data, history),
+        KeyCode::Char('n') => substitution.skip(position, data),
+        KeyCode::Char('a') => {
+            substitution.replace(position, data, history);
+            substitution.run(data, history);
+        },
+        KeyCode::Char('q') | KeyCode::Esc => {
+            finish_substitution(editor);
+            return;
+        },
+        _ => return,
+    }
+    next_confirmation(editor);
+}
+
+fn finish_change(editor: &mut Editor, outcome: vi::Outcome) {
+    // A change that goes on in insert mode, like cw, is undone together with the text typed after it
+    let buffer = editor.buffer();
+    if !outcome.insert {
+        buffer.history.break_group();
+    }
+    buffer.modified |= outcome.changed;
+    editor.input_mode |= outcome.insert;
+}
+
+fn handle_vi_key(editor: &mut Editor, c: char) {
+    // Keys of vi commands are collected until they make a whole command, or one that doesn't exist
+    editor.pending.push(c);
+    let (name, keys) = match vi::parse_register(&editor.pending) {
+        Parse::Complete((name, keys)) => (name, keys.to_string()),
+        Parse::Incomplete => return,
+        Parse::Invalid => {
+            editor.pending.clear();
+            return;
+        },
+    };
+    match vi::parse(&keys) {
+        Parse::Incomplete => {},
+        Parse::Invalid => editor.pending.clear(),
+        Parse::Complete((count, command)) => {
+            editor.pending.clear();
+            let current = editor.current();
+            let Buffer { data, history, .. } = &mut editor.buffers[current];
+            history.break_group();
+            let outcome = vi::execute(command, count, data, history, editor.registers, name);
+            finish_change(editor, outcome);
+        },
+    }
+}
+
+fn handle_visual_key(editor: &mut Editor, c: char) {
+    // In visual mode, motions move the cursor to extend the selection, and the other keys act on it
+    let Some((anchor, linewise)) = editor.selection else { return };
+    match c {
+        'v' | 'V' if editor.pending.is_empty() => {
+            // The key of the current visual mode leaves it, the other one switches to it
+            editor.selection = if linewise == (c == 'V') { None } else { Some((anchor, c == 'V')) };
+            return;
+        },
+        _ => editor.pending.push(c),
+    }
+    let (name, keys) = match vi::parse_register(&editor.pending) {
+        Parse::Complete((name, keys)) => (name, keys.to_string()),
+        Parse::Incomplete => return,
+        Parse::Invalid => {
+            editor.pending.clear();
+            return;
+        },
+    };
+    let operator = match keys.as_str() {
+        "y" => Some(Operator::Yank),
+        "d" | "x" => Some(Operator::Delete),
+        "c" => Some(Operator::Change),
+        _ => None,
+    };
+    let current = editor.current();
+    let Buffer { data, history, .. } = &mut editor.buffers[current];
+    history.break_group();
+    let outcome = match (operator, keys.as_str()) {
+        (Some(operator), _) => vi::operate_selection(operator, anchor, linewise, data, history, editor.registers, name),
+        (None, "p" | "P") => vi::put_over_selection(anchor, linewise, data, history, editor.registers, name),
+        (None, "o") => {
+            // Go to the other end of the selection
+            let position = data.position();
+            data.set_position(anchor);
+            editor.selection = Some((position, linewise));
+            editor.pending.clear();
+            return;
+        },
+        _ => {
+            match vi::parse(&keys) {
+                Parse::Incomplete => return,
+                Parse::Complete((count, command @ Command::Move(_))) => {
+                    vi::execute(command, count, data, history, editor.registers, name);
                 },
-                _ => data
+                _ => {},
             }
+            editor.pending.clear();
+            return;
+        },
+    };
+    editor.pending.clear();
+    editor.selection = None;
+    finish_change(editor, outcome);
+}
+
+fn handle_key_event(event: KeyEvent, editor: &mut Editor) {
+    // Handle the key event, editing the data in place. Undo and redo work in both modes
+    if event.kind != KeyEventKind::Press {
+        return;
+    }
+    if editor.confirm_quit {
+        editor.confirm_quit = false;
+        editor.message = None;
+        editor.quit = matches!(event.code, KeyCode::Char('y') | KeyCode::Char('Y'));
+        return;
+    }
+    if editor.substitution.is_some() {
+        handle_confirmation_key(event, editor);
+        return;
+    }
+    editor.message = None;
+    if editor.prompt.is_some() {
+        handle_prompt_key(event, editor);
+        return;
+    }
+    let control = event.modifiers == KeyModifiers::CONTROL;
+    if editor.selection.is_some() {
+        match event.code {
+            KeyCode::Char(c) if !control => handle_visual_key(editor, c),
+            KeyCode::Esc | KeyCode::Char('c') => {
+                editor.selection = None;
+                editor.pending.clear();
+            },
+            _ => {},
         }
-    } else{
-        data
+        return;
     }
+    if let KeyCode::Char(c) = event.code {
+        // Keys of the editor itself are only read at the beginning of a command
+        if !editor.input_mode && !control && (!editor.pending.is_empty() || !"siuqnNvV/?:".contains(c)) {
+            handle_vi_key(editor, c);
+            return;
+        }
+    }
+    // Typing is undone in groups, ended by anything else than typing or erasing
+    let typing = editor.input_mode && !control && matches!(event.code, KeyCode::Char(_) | KeyCode::Backspace | KeyCode::Enter);
+    let current = editor.current();
+    let position = editor.buffers[current].data.position();
+    let mut changed = false;
+    let Buffer { data, history, .. } = &mut editor.buffers[current];
+    match event.code {
+        KeyCode::Char('z') if control => changed = history.undo(data),
+        KeyCode::Char('r') | KeyCode::Char('y') if control => changed = history.redo(data),
+        _ if editor.input_mode => match event.code {
+            KeyCode::Char('c') if control => editor.input_mode = false,
+            // Control chords the editor doesn't handle don't type anything
+            KeyCode::Char(x) if !control => insert(data, history, x),
+            KeyCode::Left => data.move_left(),
+            KeyCode::Right => data.move_right(),
+            KeyCode::Up => data.move_up(),
+            KeyCode::Down => data.move_down(),
+            KeyCode::Backspace => backspace(data, history),
+            KeyCode::Enter => insert(data, history, '\n'),
+            KeyCode::Esc => editor.input_mode = false,
+            _ => return,
+        },
+        KeyCode::Char('s') => {
+            if save(editor, None) {
+                quit(editor);
+            }
+        },
+        KeyCode::Char('w') if control => {
+            let next = (editor.window + 1) % editor.windows.len();
+            editor.focus(next);
+        },
+        KeyCode::Char('q') => quit(editor),
+        KeyCode::Char('u') => changed = history.undo(data),
+        KeyCode::Char('i') => editor.input_mode = true,
+        KeyCode::Char('v') => editor.selection = Some((data.position(), false)),
+        KeyCode::Char('V') => editor.selection = Some((data.position(), true)),
+        KeyCode::Esc => editor.pending.clear(),
+        KeyCode::Char(kind @ ('/' | '?' | ':')) => editor.prompt = Some(kind.to_string()),
+        KeyCode::Char('n') => {
+            let forward = editor.last_search.as_ref().is_none_or(|(_, forward)| *forward);
+            search(editor, forward);
+        },
+        KeyCode::Char('N') => {
+            let forward = editor.last_search.as_ref().is_none_or(|(_, forward)| *forward);
+            search(editor, !forward);
+        },
+        _ => return,
+    }
+    let buffer = &mut editor.buffers[current];
+    if typing {
+        // Backspace at the beginning of the text leaves the cursor, and the text, where they were
+        changed = buffer.data.position() != position;
+    } else {
+        buffer.history.break_group();
+    }
+    buffer.modified |= changed;
 }
 
-fn create_gap_buffer() -> String {
-    // Create a gap buffer to manipulate with a file editor and return the string that the user wrote
-    let mut terminal: Stdout = stdout();
-    let mut quit: bool = false;
-    let mut data: GapBuffer = GapBuffer::new();
-    let mut input_mode: bool = false;
+pub fn edit(name: &str, data: &str, files: &mut dyn Files, registers: &mut Registers, events: &mut dyn EventSource, renderer: &mut dyn Renderer) {
+    // Run the editor on the data of the file with the given name, which may not exist yet, until the user leaves it or
+    // the events run out. Other files can then be opened from the editor
+    let mut editor = Editor::new(name, data, files, registers);
+    render(renderer, &mut editor);
 
-    let _1 = EnterAlternateScreen;
-    enable_raw_mode().expect("Raw Mode of terminal not enabled");
-    terminal.queue(MoveTo(0, 0)).unwrap();
-    reload_terminal_command_mode(&terminal, data.to_string().as_str());
-
-    while !quit {
-        while poll(Duration::ZERO).unwrap() {
-            match read().unwrap() {
+    while !editor.quit {
+        let Some(events) = events.read() else { break };
+        // The screen is drawn once for all the events read together, so that pasted text does not redraw it for every key
+        let mut redraw = false;
+        for event in events {
+            match event {
                 Event::Key(event) => {
-                    data = handle_key_event(event, &mut input_mode, &mut quit, &terminal, data.clone());
-                    if !input_mode {
-                        reload_terminal_command_mode(&terminal, data.to_string().as_str());
-                    }
-                    if quit {
+                    handle_key_event(event, &mut editor);
+                    redraw = true;
+                    if editor.quit {
                         break;
                     }
-                }
-                _ => {
-                    quit = true;
-                }
+                },
+                Event::Resize(_, _) => redraw = true,
+                _ => {},
             }
         }
-        sleep(Duration::from_millis(33));
+        if redraw && !editor.quit {
+            render(renderer, &mut editor);
+        }
     }
+}
 
-    let _2 = LeaveAlternateScreen;
-    disable_raw_mode().expect("Exit raw mode of terminal failed");
-    terminal.queue(Clear(ClearType::All)).unwrap();
-    terminal.queue(MoveTo(0, 0)).unwrap();
-    terminal.flush().unwrap();
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, VecDeque};
+    use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+    use crossterm::style::Color;
+    use super::{edit, EventSource, Files};
+    use crate::commands::screen::Screen;
+    use crate::commands::vi::Registers;
 
-    data.to_string()
-}
+    #[derive(Default)]
+    struct MemoryFiles(HashMap<String, String>);
+
+    impl Files for MemoryFiles {
+        fn read(&mut self, path: &str) -> Result<Option<String>, String> {
+            Ok(self.0.get(path).cloned())
+        }
+
+        fn write(&mut self, path: &str, data: &str) -> Result<(), String> {
+            self.0.insert(path.to_string(), data.to_string());
+            Ok(())
+        }
+    }
+
+    struct Script(VecDeque<Event>);
+
+    impl EventSource for Script {
+        fn read(&mut self) -> Option<Vec<Event>> {
+            self.0.pop_front().map(|event| vec![event])
+        }
+    }
+
+    fn script(keys: &str) -> Script {
+        // Key events typing the text, where <Esc>, <Enter>, <BS> and <C-x> stand for those keys
+        let mut events = VecDeque::new();
+        let mut rest = keys;
+        while let Some(c) = rest.chars().next() {
+            let special = rest.strip_prefix('<').and_then(|tail| tail.split_once('>')).and_then(|(name, tail)| {
+                let key = match name {
+                    "Esc" => (KeyCode::Esc, KeyModifiers::NONE),
+                    "Enter" => (KeyCode::Enter, KeyModifiers::NONE),
+                    "BS" => (KeyCode::Backspace, KeyModifiers::NONE),
+                    _ => (KeyCode::Char(name.strip_prefix("C-")?.chars().next()?), KeyModifiers::CONTROL),
+                };
+                Some((key, tail))
+            });
+            let ((code, modifiers), tail) = special.unwrap_or(((KeyCode::Char(c), KeyModifiers::NONE), &rest[c.len_utf8()..]));
+            events.push_back(Event::Key(KeyEvent::new(code, modifiers)));
+            rest = tail;
+        }
+        Script(events)
+    }
+
+    fn run(files: &mut MemoryFiles, name: &str, keys: &str) -> (Screen, Script) {
+        // Edit the file on a screen of 40 by 10 with the keys, returning the last drawing and the keys left unread
+        let data = files.0.get(name).cloned().unwrap_or_default();
+        let mut screen = Screen::new(40, 10);
+        let mut script = script(keys);
+        edit(name, &data, files, &mut Registers::default(), &mut script, &mut screen);
+        (screen, script)
+    }
+
+    #[test]
+    fn typed_text_is_drawn_and_saved() {
+        let mut files = MemoryFiles::default();
+        let (screen, _) = run(&mut files, "a.txt", "ihello<Enter>world<BS>d<Esc>:w<Enter>");
+        assert_eq!(files.0["a.txt"], "hello\nworld");
+        assert_eq!(screen.line(0), "1 hello");
+        assert_eq!(screen.line(1), "2 world");
+        assert!(screen.line(8).starts_with(" a.txt───"));
+        assert!(screen.line(8).ends_with("Ln 2, Col 6  COMMAND"));
+        assert_eq!(screen.line(9), "\"a.txt\" written");
+        assert_eq!(screen.cursor(), (7, 1));
+    }
+
+    #[test]
+    fn commands_and_undo_edit_the_text() {
+        let mut files = MemoryFiles::default();
+        files.0.insert(String::from("a.txt"), String::from("one two\nthree"));
+        let (screen, _) = run(&mut files, "a.txt", "dwjxu:s/three/four/<Enter>");
+        assert_eq!(screen.line(0), "1 two");
+        assert_eq!(screen.line(1), "2 four");
+        assert!(screen.line(8).starts_with(" a.txt [+]"));
+        let (screen, _) = run(&mut files, "a.txt", "dwuu");
+        assert_eq!(screen.line(0), "1 one two");
+        assert_eq!(screen.line(9), "S: Save | Q: Quit | I: Insert | U: Undo");
+    }
+
+    #[test]
+    fn search_matches_are_highlighted() {
+        let mut files = MemoryFiles::default();
+        files.0.insert(String::from("a.txt"), String::from("cat dog cat"));
+        let (screen, _) = run(&mut files, "a.txt", "/cat<Enter>");
+        assert_eq!(screen.cursor(), (10, 0));
+        let reversed: Vec<usize> = (0..40).filter(|col| screen.cell(*col, 0).reverse).collect();
+        assert_eq!(reversed, vec![2, 3, 4, 10, 11, 12]);
+    }
+
+    #[test]
+    fn substitutions_wait_for_confirmation() {
+        let mut files = MemoryFiles::default();
+        files.0.insert(String::from("a.txt"), String::from("x x x"));
+        let (screen, _) = run(&mut files, "a.txt", ":s/x/y/gc<Enter>yn");
+        assert_eq!(screen.line(0), "1 y x x");
+        assert_eq!(screen.line(9), "Replace with y (y/n/a/q)?");
+        assert_eq!(screen.cursor(), (6, 0));
+        let (screen, _) = run(&mut files, "a.txt", ":s/x/y/gc<Enter>nyq");
+        assert_eq!(screen.line(0), "1 x y x");
+        assert_eq!(screen.line(9), "1 substitution");
+    }
 
-pub fn create_new_file(name: String, parent_inode: &mut Inode) -> Result<(), &'static str> {
-    // Create a new file with the given name and add it to the hard link, which must be a directory
+    #[test]
+    fn quitting_asks_to_discard_changes() {
+        let mut files = MemoryFiles::default();
+        let (screen, script) = run(&mut files, "a.txt", "ia<Esc>q");
+        assert_eq!(screen.line(9), "Discard the unsaved changes? (y/n)");
+        assert!(script.0.is_empty());
+        let (screen, script) = run(&mut files, "a.txt", "ia<Esc>qnqyib");
+        assert_eq!(screen.line(0), "1 a");
+        assert_eq!(script.0.len(), 2);
+        assert!(files.0.is_empty());
+    }
 
-    let file_data: String = create_gap_buffer();
+    #[test]
+    fn unhandled_control_keys_type_nothing() {
+        let mut files = MemoryFiles::default();
+        files.0.insert(String::from("a.txt"), String::from("a"));
+        let (screen, script) = run(&mut files, "a.txt", "i<C-b><C-k><Esc>qx");
+        assert_eq!(screen.line(0), "1 a");
+        assert_eq!(script.0.len(), 1);
+        let (screen, _) = run(&mut files, "a.txt", "A<C-b>b<Esc>");
+        assert_eq!(screen.line(0), "1 ab");
+        assert!(screen.line(8).starts_with(" a.txt [+]"));
+    }
 
-    let inode_file = Inode::new_file_with_data(name, file_data);
+    #[test]
+    fn windows_show_their_own_files() {
+        let mut files = MemoryFiles::default();
+        files.0.insert(String::from("a.txt"), String::from("left"));
+        files.0.insert(String::from("b.txt"), String::from("right"));
+        let (screen, _) = run(&mut files, "a.txt", ":vs b.txt<Enter><C-w>x:wa<Enter>");
+        assert_eq!(screen.line(0), "1 eft              │1 right");
+        assert!(screen.line(8).starts_with(" a.txt Ln 1, Col 1"));
+        assert!(screen.line(8).contains("│ b.txt────"));
+        assert_eq!(screen.cursor(), (2, 0));
+        assert_eq!(files.0["a.txt"], "eft");
 
-    if parent_inode.is_file(){
-        return Err("The hard link is a file, it should be a directory");
+        let (screen, _) = run(&mut files, "a.txt", ":e b.txt<Enter>:ls<Enter>");
+        assert_eq!(screen.line(0), "1 right");
+        assert_eq!(screen.line(9), "1 a.txt  2 %b.txt");
+        let (screen, _) = run(&mut files, "a.txt", ":sp<Enter>:bn<Enter>");
+        assert_eq!(screen.line(0), "1 eft");
+        assert_eq!(screen.line(4), "1 eft");
     }
 
-    parent_inode.add_inode(inode_file);
+    #[test]
+    fn windows_on_the_same_file_keep_their_view() {
+        // The cursor of the buffer can be below or right of the view of the window without the focus
+        let mut files = MemoryFiles::default();
+        let lines: Vec<String> = (1..=50).map(|line| line.to_string()).collect();
+        files.0.insert(String::from("a.txt"), lines.join("\n"));
+        files.0.insert(String::from("b.txt"), "x".repeat(100));
+        let (screen, _) = run(&mut files, "a.txt", ":sp<Enter>G<C-w>");
+        assert_eq!(screen.line(0), " 1 1");
+        assert_eq!(screen.line(4), "47 47");
+        assert_eq!(screen.cursor(), (3, 0));
+        let (screen, _) = run(&mut files, "b.txt", ":vs<Enter>$<C-w>");
+        assert!(screen.line(0).starts_with("1 xxxxxxxxxxxxxxxxx│1 xxx"));
+        assert_eq!(screen.cursor(), (2, 0));
+    }
 
-    return Ok(());
-}
\ No newline at end of file
+    #[test]
+    fn source_files_are_colored() {
+        let mut files = MemoryFiles::default();
+        files.0.insert(String::from("a.rs"), String::from("fn main"));
+        let (screen, _) = run(&mut files, "a.rs", "");
+        assert_eq!(screen.cell(2, 0).color, Some(Color::Magenta));
+        assert_eq!(screen.cell(5, 0).color, None);
+    }
+}
@@ -0,0 +1,247 @@
+use crate::session::{Session, ROOT_UID};
+use crate::types::{AclEntry, AclTag, Inode, IMMUTABLE, READ_ONLY, SET_GID, SET_UID, STICKY};
+use crate::users;
+use crate::utils;
+
+pub(super) fn split_recursive(commands: &[String]) -> (bool, &[String]) {
+    if commands.first().map(String::as_str) == Some("-R") {
+        (true, &commands[1..])
+    } else {
+        (false, commands)
+    }
+}
+
+pub(super) fn check_owner(session: &Session, inode: &Inode, path: &str, recursive: bool) -> Result<(), String> {
+    // Only the owner of an inode, or root, may change its permissions or ownership, and never of immutable inodes
+    if session.uid != ROOT_UID && inode.get_uid() != session.uid {
+        return Err(String::from("Operation not permitted, only the owner or root can change it"));
+    }
+    if inode.has_attribute(IMMUTABLE) {
+        return Err(format!("'{}' is immutable", path));
+    }
+    if recursive {
+        for child in inode.get_children() {
+            check_owner(session, child, &format!("{}/{}", path.trim_end_matches('/'), child.get_name()), recursive)?;
+        }
+    }
+    Ok(())
+}
+
+pub(super) fn apply(inode: &mut Inode, recursive: bool, function: &mut dyn FnMut(&mut Inode)) {
+    if recursive {
+        inode.walk_mut(function);
+    } else {
+        function(inode);
+    }
+}
+
+pub fn parse_mode(mode: &str, current: u16, is_directory: bool) -> Result<u16, &'static str> {
+    // Parse an octal mode (like 755) or a symbolic one (like u+x,go-w) relative to the current permissions
+    if !mode.is_empty() && mode.len() <= 4 && mode.chars().all(|c| ('0'..='7').contains(&c)) {
+        return u16::from_str_radix(mode, 8).map_err(|_| "Invalid mode");
+    }
+
+    let mut permissions = current;
+    for clause in mode.split(',') {
+        let operators_start = clause.find(['+', '-', '=']).ok_or("Invalid mode")?;
+        let (who, mut actions) = clause.split_at(operators_start);
+        let mut mask: u16 = 0;
+        for c in who.chars() {
+            mask |= match c {
+                'u' => 0o4700,
+                'g' => 0o2070,
+                'o' => 0o1007,
+                'a' => 0o7777,
+                _ => return Err("Invalid mode"),
+            };
+        }
+        if mask == 0 {
+            mask = 0o7777;
+        }
+        while let Some(operator) = actions.chars().next() {
+            let end = actions[1..].find(['+', '-', '=']).map(|index| index + 1).unwrap_or(actions.len());
+            let mut bits: u16 = 0;
+            for c in actions[1..end].chars() {
+                bits |= match c {
+                    'r' => 0o444,
+                    'w' => 0o222,
+                    'x' => 0o111,
+                    'X' if is_directory || current & 0o111 != 0 => 0o111,
+                    'X' => 0,
+                    's' => SET_UID | SET_GID,
+                    't' => STICKY,
+                    _ => return Err("Invalid mode"),
+                };
+            }
+            bits &= mask;
+            match operator {
+                '+' => permissions |= bits,
+                '-' => permissions &= !bits,
+                '=' => permissions = (permissions & !mask) | bits,
+                _ => return Err("Invalid mode"),
+            }
+            actions = &actions[end..];
+        }
+    }
+    Ok(permissions)
+}
+
+pub fn handle_chmod(commands: Vec<String>, root_inode: &mut Inode, session: &Session) -> Result<(), String> {
+    let (recursive, arguments) = split_recursive(&commands);
+    if arguments.len() != 2 {
+        return Err("Invalid number of arguments, type 'help chmod' to see the usage of the command".into());
+    }
+    let mode = arguments[0].clone();
+    // Validate the mode before changing anything
+    parse_mode(&mode, 0, false)?;
+    let inode = session.resolve_mut(root_inode, &utils::normalize_path(&arguments[1]))?;
+    check_owner(session, inode, &arguments[1], recursive)?;
+    apply(inode, recursive, &mut |inode: &mut Inode| {
+        if let Ok(permissions) = parse_mode(&mode, inode.get_permissions(), inode.is_directory()) {
+            inode.set_permissions(permissions);
+            // As in POSIX, the group bits set the mask of an ACL, so that they limit the named entries too
+            if !inode.get_acl().is_empty() {
+                let mut acl = inode.get_acl().to_vec();
+                match acl.iter_mut().find(|entry| entry.tag == AclTag::Mask) {
+                    Some(mask) => mask.permissions = (permissions >> 3) & 0o7,
+                    None => acl.push(AclEntry { tag: AclTag::Mask, permissions: (permissions >> 3) & 0o7 }),
+                }
+                inode.set_acl(acl);
+            }
+        }
+    });
+    Ok(())
+}
+
+pub(super) fn parse_user(root_inode: &Inode, name: &str) -> Result<u32, &'static str> {
+    match users::find_user(root_inode, name) {
+        Some(user) => Ok(user.uid),
+        None => name.parse().map_err(|_| "User not found"),
+    }
+}
+
+pub(super) fn parse_group(root_inode: &Inode, name: &str) -> Result<u32, &'static str> {
+    match users::find_group(root_inode, name) {
+        Some(group) => Ok(group.gid),
+        None => name.parse().map_err(|_| "Group not found"),
+    }
+}
+
+fn change_owner(root_inode: &mut Inode, session: &Session, path: &str, uid: Option<u32>, gid: Option<u32>, recursive: bool) -> Result<(), String> {
+    // Like in unix, only root can give files to another user, which would also move them out of the quota of their owner.
+    // As the files must be owned by the user, naming itself changes nothing
+    if uid.is_some_and(|uid| session.uid != ROOT_UID && uid != session.uid) {
+        return Err("Operation not permitted, only root can change the owner".into());
+    }
+    if let Some(gid) = gid {
+        // Like in unix, users other than root can only give files to a group they belong to
+        if session.uid != ROOT_UID && !session.in_group(gid) {
+            return Err("Operation not permitted, you are not a member of the group".into());
+        }
+    }
+    let inode = session.resolve_mut(root_inode, &utils::normalize_path(path))?;
+    check_owner(session, inode, path, recursive)?;
+    apply(inode, recursive, &mut |inode: &mut Inode| {
+        inode.set_owner(uid.unwrap_or(inode.get_uid()), gid.unwrap_or(inode.get_gid()));
+    });
+    Ok(())
+}
+
+pub fn handle_chown(commands: Vec<String>, root_inode: &mut Inode, session: &Session) -> Result<(), String> {
+    let (recursive, arguments) = split_recursive(&commands);
+    if arguments.len() != 2 {
+        return Err("Invalid number of arguments, type 'help chown' to see the usage of the command".into());
+    }
+    let (user, group) = match arguments[0].split_once(':') {
+        Some((user, group)) => (user, Some(group)),
+        None => (arguments[0].as_str(), None),
+    };
+    let uid = parse_user(root_inode, user)?;
+    let gid = match group {
+        Some(group) if !group.is_empty() => Some(parse_group(root_inode, group)?),
+        _ => None,
+    };
+    change_owner(root_inode, session, &arguments[1], Some(uid), gid, recursive)
+}
+
+pub fn handle_chgrp(commands: Vec<String>, root_inode: &mut Inode, session: &Session) -> Result<(), String> {
+    let (recursive, arguments) = split_recursive(&commands);
+    if arguments.len() != 2 {
+        return Err("Invalid number of arguments, type 'help chgrp' to see the usage of the command".into());
+    }
+    let gid = parse_group(root_inode, &arguments[0])?;
+    change_owner(root_inode, session, &arguments[1], None, Some(gid), recursive)
+}
+
+fn parse_switch(value: &str) -> Result<bool, &'static str> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err("Expected 'on' or 'off'"),
+    }
+}
+
+pub fn handle_readonly(commands: Vec<String>, root_inode: &mut Inode, session: &Session) -> Result<(), String> {
+    if commands.len() != 2 {
+        return Err("Invalid number of arguments, type 'help readonly' to see the usage of the command".into());
+    }
+    let enabled = parse_switch(&commands[0])?;
+    let inode = session.resolve_mut(root_inode, &utils::normalize_path(&commands[1]))?;
+    check_owner(session, inode, &commands[1], false)?;
+    inode.set_attribute(READ_ONLY, enabled);
+    Ok(())
+}
+
+pub fn handle_immutable(commands: Vec<String>, root_inode: &mut Inode, session: &Session) -> Result<(), String> {
+    if commands.len() != 2 {
+        return Err("Invalid number of arguments, type 'help immutable' to see the usage of the command".into());
+    }
+    let enabled = parse_switch(&commands[0])?;
+    if session.uid != ROOT_UID {
+        return Err("Operation not permitted, only root can change the immutable flag".into());
+    }
+    let inode = session.resolve_mut(root_inode, &utils::normalize_path(&commands[1]))?;
+    inode.set_attribute(IMMUTABLE, enabled);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_mode;
+    use crate::types::{SET_GID, SET_UID, STICKY};
+
+    #[test]
+    fn octal_modes_replace_the_permissions() {
+        assert_eq!(parse_mode("755", 0o600, false), Ok(0o755));
+        assert_eq!(parse_mode("1777", 0o755, true), Ok(0o777 | STICKY));
+        assert_eq!(parse_mode("0", 0o644, false), Ok(0));
+        assert!(parse_mode("789", 0o644, false).is_err());
+        assert!(parse_mode("17777", 0o644, false).is_err());
+    }
+
+    #[test]
+    fn symbolic_modes_change_the_permissions() {
+        assert_eq!(parse_mode("u+x,go-w", 0o666, false), Ok(0o744));
+        assert_eq!(parse_mode("+x", 0o644, false), Ok(0o755));
+        assert_eq!(parse_mode("g=r", 0o674, false), Ok(0o644));
+        assert_eq!(parse_mode("u=rw,o=", 0o777, false), Ok(0o670));
+        assert_eq!(parse_mode("u-w+x", 0o644, false), Ok(0o544));
+        assert_eq!(parse_mode("o+t", 0o777, true), Ok(0o777 | STICKY));
+        assert_eq!(parse_mode("ug+s", 0o755, false), Ok(0o755 | SET_UID | SET_GID));
+        assert_eq!(parse_mode("g+s", 0o755, true), Ok(0o755 | SET_GID));
+    }
+
+    #[test]
+    fn capital_x_only_applies_to_directories_and_executables() {
+        assert_eq!(parse_mode("a+X", 0o644, false), Ok(0o644));
+        assert_eq!(parse_mode("a+X", 0o744, false), Ok(0o755));
+        assert_eq!(parse_mode("a+X", 0o644, true), Ok(0o755));
+    }
+
+    #[test]
+    fn invalid_modes_are_rejected() {
+        for mode in ["", "u", "z+x", "u+q", "u+x,"] {
+            assert!(parse_mode(mode, 0o644, false).is_err(), "{}", mode);
+        }
+    }
+}
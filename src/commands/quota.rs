@@ -0,0 +1,102 @@
+use std::io::Write;
+use crate::quota::{self, Usage};
+use crate::session::{Session, ROOT_UID};
+use crate::types::{Inode, Quota, EXECUTE};
+use crate::users;
+use crate::utils;
+
+fn parse_limit(value: &str) -> Result<u64, &'static str> {
+    // A number, optionally followed by K, M or G for multiples of 1024
+    let (number, multiplier) = match value.chars().last() {
+        Some('K') | Some('k') => (&value[..value.len() - 1], 1 << 10),
+        Some('M') | Some('m') => (&value[..value.len() - 1], 1 << 20),
+        Some('G') | Some('g') => (&value[..value.len() - 1], 1 << 30),
+        _ => (value, 1),
+    };
+    let number: u64 = number.parse().map_err(|_| "Invalid limit, expected a number optionally followed by K, M or G")?;
+    number.checked_mul(multiplier).ok_or("Limit too large")
+}
+
+fn format_limit(limit: u64) -> String {
+    if limit == 0 {
+        String::from("-")
+    } else {
+        limit.to_string()
+    }
+}
+
+fn write_usage(output: &mut dyn Write, quota: &Quota, usage: &Usage) {
+    writeln!(output, "  {:<8} {:>10} {:>10} {:>10}", "", "used", "soft", "hard").unwrap();
+    writeln!(output, "  {:<8} {:>10} {:>10} {:>10}", "bytes", usage.bytes, format_limit(quota.soft_bytes), format_limit(quota.hard_bytes)).unwrap();
+    writeln!(output, "  {:<8} {:>10} {:>10} {:>10}", "inodes", usage.inodes, format_limit(quota.soft_inodes), format_limit(quota.hard_inodes)).unwrap();
+}
+
+fn collect_directory_quotas(inode: &Inode, path: String, session: &Session, quotas: &mut Vec<(String, Quota, Usage)>) {
+    // Directories with a quota, among the ones the session can reach
+    if let Some(quota) = inode.get_quota() {
+        quotas.push((if path.is_empty() { String::from("/") } else { path.clone() }, quota, quota::tree_usage(inode)));
+    }
+    if !session.can_access(inode, EXECUTE) {
+        return;
+    }
+    for child in inode.get_children().iter().filter(|child| child.is_directory()) {
+        collect_directory_quotas(child, format!("{}/{}", path, child.get_name()), session, quotas);
+    }
+}
+
+pub fn handle_quota(commands: Vec<String>, root_inode: &mut Inode, session: &Session, output: &mut dyn Write) -> Result<(), String> {
+    if commands.len() > 1 {
+        return Err("Invalid number of arguments, type 'help quota' to see the usage of the command".into());
+    }
+    let name = commands.first().unwrap_or(&session.user);
+    if name != &session.user && session.uid != ROOT_UID {
+        return Err("Only root can see the quotas of other users".into());
+    }
+    let user = users::find_user(root_inode, name).ok_or("User not found")?;
+    writeln!(output, "Disk quotas for user '{}':", user.name).unwrap();
+    match quota::user_quota(root_inode, &user.name) {
+        Some(quota) => write_usage(output, &quota, &quota::user_usage(root_inode, user.uid)),
+        None => writeln!(output, "  none").unwrap(),
+    }
+
+    let mut quotas = Vec::new();
+    collect_directory_quotas(root_inode, String::new(), session, &mut quotas);
+    for (path, quota, usage) in quotas {
+        writeln!(output, "Disk quotas for directory '{}':", path).unwrap();
+        write_usage(output, &quota, &usage);
+    }
+    Ok(())
+}
+
+pub fn handle_setquota(commands: Vec<String>, root_inode: &mut Inode, session: &Session) -> Result<(), String> {
+    let directory = commands.first().map(String::as_str) == Some("-d");
+    let arguments = if directory { &commands[1..] } else { &commands[..] };
+    let quota = match arguments.len() {
+        2 if arguments[1] == "off" => None,
+        5 => Some(Quota {
+            soft_bytes: parse_limit(&arguments[1])?,
+            hard_bytes: parse_limit(&arguments[2])?,
+            soft_inodes: parse_limit(&arguments[3])?,
+            hard_inodes: parse_limit(&arguments[4])?,
+        }),
+        _ => return Err("Invalid number of arguments, type 'help setquota' to see the usage of the command".into()),
+    };
+    if session.uid != ROOT_UID {
+        return Err("Only root can set quotas".into());
+    }
+
+    if directory {
+        let inode = session.resolve_mut(root_inode, &utils::normalize_path(&arguments[0]))?;
+        if !inode.is_directory() {
+            return Err(format!("'{}' is not a directory", arguments[0]));
+        }
+        inode.set_quota(quota);
+    } else {
+        let user = users::find_user(root_inode, &arguments[0]).ok_or("User not found")?;
+        if user.uid == ROOT_UID {
+            return Err("The root user can't have a quota".into());
+        }
+        quota::set_user_quota(root_inode, &user.name, quota);
+    }
+    Ok(())
+}
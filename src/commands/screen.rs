@@ -0,0 +1,89 @@
+use crossterm::style::Color;
+use unicode_segmentation::UnicodeSegmentation;
+use super::gap_buffer;
+use super::new_file::Renderer;
+
+#[derive(Clone, Default)]
+pub struct Cell {
+    // A column of the screen. The column right of a wide character is empty
+    pub text: String,
+    pub color: Option<Color>,
+    pub reverse: bool,
+}
+
+pub struct Screen {
+    // Renderer drawing in memory, keeping the last complete drawing to be looked at
+    width: usize,
+    height: usize,
+    cells: Vec<Vec<Cell>>,
+    position: (usize, usize),
+    color: Option<Color>,
+    reverse: bool,
+    shown: Vec<Vec<Cell>>,
+    cursor: (usize, usize),
+}
+
+impl Screen {
+    pub fn new(width: usize, height: usize) -> Screen {
+        let cells = vec![vec![Cell { text: String::from(" "), ..Cell::default() }; width]; height];
+        Screen { width, height, cells: cells.clone(), position: (0, 0), color: None, reverse: false, shown: cells, cursor: (0, 0) }
+    }
+
+    pub fn line(&self, row: usize) -> String {
+        // Text of the row, without the spaces at its end
+        let line: String = self.shown[row].iter().map(|cell| cell.text.as_str()).collect();
+        line.trim_end().to_string()
+    }
+
+    pub fn cell(&self, col: usize, row: usize) -> &Cell {
+        &self.shown[row][col]
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        self.cursor
+    }
+}
+
+impl Renderer for Screen {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn clear(&mut self) {
+        for row in &mut self.cells {
+            row.fill(Cell { text: String::from(" "), ..Cell::default() });
+        }
+    }
+
+    fn move_to(&mut self, col: usize, row: usize) {
+        self.position = (col, row);
+    }
+
+    fn write(&mut self, text: &str) {
+        // Text going past the right edge is lost, like on a terminal without line wrapping
+        for grapheme in text.graphemes(true) {
+            let (col, row) = self.position;
+            let width = gap_buffer::grapheme_width(grapheme);
+            for offset in 0..width {
+                if row < self.height && col + offset < self.width {
+                    let text = if offset == 0 { grapheme.to_string() } else { String::new() };
+                    self.cells[row][col + offset] = Cell { text, color: self.color, reverse: self.reverse };
+                }
+            }
+            self.position = (col + width, row);
+        }
+    }
+
+    fn set_color(&mut self, color: Option<Color>) {
+        self.color = color;
+    }
+
+    fn set_reverse(&mut self, reverse: bool) {
+        self.reverse = reverse;
+    }
+
+    fn flush(&mut self) {
+        self.shown = self.cells.clone();
+        self.cursor = self.position;
+    }
+}
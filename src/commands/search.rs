@@ -0,0 +1,204 @@
+use super::gap_buffer::GapBuffer;
+use super::history::History;
+
+pub fn find(data: &GapBuffer, pattern: &str, from: usize, forward: bool) -> Option<(usize, bool)> {
+    // Next match in the direction, wrapping around the end of the text. The flag tells whether it wrapped
+    match data.find(pattern, from, forward) {
+        Some(position) => Some((position, false)),
+        None => {
+            let restart = if forward { 0 } else { data.char_count() };
+            data.find(pattern, restart, forward).map(|position| (position, true))
+        },
+    }
+}
+
+pub fn highlights(line: &str, pattern: &str) -> Vec<bool> {
+    // For every character of the line, whether it is part of a match of the pattern
+    let line: Vec<char> = line.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    let mut highlighted = vec![false; line.len()];
+    if pattern.is_empty() {
+        return highlighted;
+    }
+    let mut start = 0;
+    while start + pattern.len() <= line.len() {
+        if line[start..start + pattern.len()] == pattern[..] {
+            highlighted[start..start + pattern.len()].fill(true);
+            start += pattern.len();
+        } else {
+            start += 1;
+        }
+    }
+    highlighted
+}
+
+fn split_fields(text: &str, delimiter: char) -> Vec<String> {
+    // Split on the delimiter, which can be escaped with a backslash inside a field
+    let mut fields = vec![String::new()];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(next) if next == delimiter || next == '\\' => fields.last_mut().unwrap().push(next),
+                Some(next) => {
+                    fields.last_mut().unwrap().push('\\');
+                    fields.last_mut().unwrap().push(next);
+                },
+                None => fields.last_mut().unwrap().push('\\'),
+            },
+            c if c == delimiter => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+pub struct Substitution {
+    // A substitute command in progress, replacing the matches one after the other in the range
+    pub pattern: String,
+    pub replacement: String,
+    global: bool, // every match of a line instead of the first one
+    pub confirm: bool,
+    next: usize, // where the next match is searched from
+    end: usize,  // end of the range, moved by the replacements
+    pub count: usize,
+}
+
+impl Substitution {
+    pub fn parse(command: &str, data: &GapBuffer) -> Result<Substitution, String> {
+        // Parse s/pattern/replacement/flags on the line of the cursor, or %s/... on the whole text
+        let (whole, rest) = match command.strip_prefix('%') {
+            Some(rest) => (true, rest),
+            None => (false, command),
+        };
+        let rest = rest.strip_prefix('s').ok_or(format!("Not an editor command: {}", command))?;
+        let delimiter = rest.chars().next().ok_or("Usage: s/pattern/replacement/[gc]")?;
+        if delimiter.is_alphanumeric() || delimiter.is_whitespace() || delimiter == '\\' {
+            return Err(String::from("Invalid delimiter for the substitute command"));
+        }
+        let fields = split_fields(&rest[delimiter.len_utf8()..], delimiter);
+        if fields.len() > 3 {
+            return Err(String::from("Trailing characters after the substitute command"));
+        }
+        let pattern = fields[0].clone();
+        if pattern.is_empty() {
+            return Err(String::from("Empty pattern"));
+        }
+        let replacement = fields.get(1).cloned().unwrap_or_default();
+        let flags = fields.get(2).cloned().unwrap_or_default();
+        if let Some(flag) = flags.chars().find(|flag| !"gc".contains(*flag)) {
+            return Err(format!("Invalid flag: {}", flag));
+        }
+
+        let (start, end) = if whole {
+            (0, data.char_count())
+        } else {
+            let start = data.position() - data.col_index();
+            (start, data.find("\n", start, true).unwrap_or(data.char_count()))
+        };
+        Ok(Substitution {
+            pattern,
+            replacement,
+            global: flags.contains('g'),
+            confirm: flags.contains('c'),
+            next: start,
+            end,
+            count: 0,
+        })
+    }
+
+    pub fn next_match(&self, data: &GapBuffer) -> Option<usize> {
+        let length = self.pattern.chars().count();
+        data.find(&self.pattern, self.next, true).filter(|position| position + length <= self.end)
+    }
+
+    fn advance(&mut self, after: usize, data: &GapBuffer) {
+        // Without the g flag, the search goes on from the next line
+        self.next = if self.global {
+            after
+        } else {
+            data.find("\n", after, true).map_or(self.end, |position| position + 1)
+        };
+    }
+
+    pub fn replace(&mut self, position: usize, data: &mut GapBuffer, history: &mut History) {
+        let length = self.pattern.chars().count();
+        let replacement_length = self.replacement.chars().count();
+        data.set_position(position);
+        let removed = data.delete_forward(length);
+        history.record_delete(position, &removed, position);
+        data.push_str(&self.replacement);
+        history.record_insert(position, &self.replacement);
+        self.end = self.end + replacement_length - length;
+        self.count += 1;
+        self.advance(position + replacement_length, data);
+    }
+
+    pub fn skip(&mut self, position: usize, data: &GapBuffer) {
+        self.advance(position + self.pattern.chars().count(), data);
+    }
+
+    pub fn run(&mut self, data: &mut GapBuffer, history: &mut History) {
+        // Replace every remaining match without asking
+        while let Some(position) = self.next_match(data) {
+            self.replace(position, data, history);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find, Substitution};
+    use crate::commands::gap_buffer::GapBuffer;
+    use crate::commands::history::History;
+
+    fn substitute(text: &str, line: usize, command: &str) -> Result<String, String> {
+        let mut data = GapBuffer::from_string(text);
+        let mut history = History::new();
+        for _ in 0..line {
+            data.move_down();
+        }
+        let mut substitution = Substitution::parse(command, &data)?;
+        substitution.run(&mut data, &mut history);
+        Ok(data.to_string())
+    }
+
+    #[test]
+    fn search_wraps_around() {
+        let data = GapBuffer::from_string("abc abc");
+        assert_eq!(find(&data, "abc", 1, true), Some((4, false)));
+        assert_eq!(find(&data, "abc", 5, true), Some((0, true)));
+        assert_eq!(find(&data, "abc", 0, false), Some((4, true)));
+        assert_eq!(find(&data, "xyz", 0, true), None);
+    }
+
+    #[test]
+    fn substitute_respects_the_range_and_flags() {
+        let text = "a a\na a\na a";
+        assert_eq!(substitute(text, 1, "s/a/b/").unwrap(), "a a\nb a\na a");
+        assert_eq!(substitute(text, 1, "s/a/b/g").unwrap(), "a a\nb b\na a");
+        assert_eq!(substitute(text, 0, "%s/a/b/").unwrap(), "b a\nb a\nb a");
+        assert_eq!(substitute(text, 0, "%s/a/bb/g").unwrap(), "bb bb\nbb bb\nbb bb");
+        assert_eq!(substitute("a/b", 0, "s#/#\\##").unwrap(), "a#b");
+        assert_eq!(substitute("aaa", 0, "s/a/aa/g").unwrap(), "aaaaaa");
+    }
+
+    #[test]
+    fn substitute_rejects_invalid_commands() {
+        assert!(substitute("a", 0, "s//b/").is_err());
+        assert!(substitute("a", 0, "s/a/b/x").is_err());
+        assert!(substitute("a", 0, "s/a/b/g/").is_err());
+        assert!(substitute("a", 0, "sa").is_err());
+    }
+
+    #[test]
+    fn substitutions_are_undone_together() {
+        let mut data = GapBuffer::from_string("one two one");
+        let mut history = History::new();
+        let mut substitution = Substitution::parse("s/one/three/g", &data).unwrap();
+        substitution.run(&mut data, &mut history);
+        assert_eq!(data.to_string(), "three two three");
+        history.undo(&mut data);
+        assert_eq!(data.to_string(), "one two one");
+    }
+}
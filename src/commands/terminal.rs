@@ -0,0 +1,75 @@
+use std::io::{stdin, stdout, IsTerminal, Stdout, Write};
+use std::time::Duration;
+use crossterm::event::{Event, poll, read};
+use crossterm::{QueueableCommand, terminal};
+use crossterm::cursor::MoveTo;
+use crossterm::style::{Attribute, Color, SetAttribute, SetForegroundColor};
+use crossterm::terminal::{Clear, ClearType, disable_raw_mode, enable_raw_mode};
+use super::new_file::{self, EventSource, Files, Renderer};
+use super::vi::Registers;
+
+struct TerminalEvents;
+
+impl EventSource for TerminalEvents {
+    fn read(&mut self) -> Option<Vec<Event>> {
+        // Wait for an event, then take the ones already pending with it
+        let mut events = vec![read().ok()?];
+        while poll(Duration::ZERO).unwrap_or(false) {
+            events.push(read().ok()?);
+        }
+        Some(events)
+    }
+}
+
+struct TerminalRenderer {
+    terminal: Stdout,
+}
+
+impl Renderer for TerminalRenderer {
+    fn size(&self) -> (usize, usize) {
+        let (w, h) = terminal::size().unwrap();
+        (w as usize, h as usize)
+    }
+
+    fn clear(&mut self) {
+        self.terminal.queue(Clear(ClearType::All)).unwrap();
+    }
+
+    fn move_to(&mut self, col: usize, row: usize) {
+        self.terminal.queue(MoveTo(col as u16, row as u16)).unwrap();
+    }
+
+    fn write(&mut self, text: &str) {
+        self.terminal.write_all(text.as_bytes()).unwrap();
+    }
+
+    fn set_color(&mut self, color: Option<Color>) {
+        self.terminal.queue(SetForegroundColor(color.unwrap_or(Color::Reset))).unwrap();
+    }
+
+    fn set_reverse(&mut self, reverse: bool) {
+        self.terminal.queue(SetAttribute(if reverse { Attribute::Reverse } else { Attribute::NoReverse })).unwrap();
+    }
+
+    fn flush(&mut self) {
+        self.terminal.flush().unwrap();
+    }
+}
+
+pub fn edit_file(name: &str, data: &str, files: &mut dyn Files, registers: &mut Registers) -> Result<(), String> {
+    // Open the editor in the terminal, in raw mode, and restore the terminal when it is left
+    if !stdin().is_terminal() || !stdout().is_terminal() {
+        return Err(String::from("The editor needs a terminal"));
+    }
+    enable_raw_mode().map_err(|error| format!("Raw mode of the terminal not enabled: {}", error))?;
+    let mut renderer = TerminalRenderer { terminal: stdout() };
+
+    new_file::edit(name, data, files, registers, &mut TerminalEvents, &mut renderer);
+
+    let terminal = &mut renderer.terminal;
+    disable_raw_mode().expect("Exit raw mode of terminal failed");
+    terminal.queue(Clear(ClearType::All)).unwrap();
+    terminal.queue(MoveTo(0, 0)).unwrap();
+    terminal.flush().unwrap();
+    Ok(())
+}
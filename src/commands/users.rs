@@ -0,0 +1,189 @@
+use std::io::Write;
+use crate::line_editor::read_password;
+use crate::quota;
+use crate::session::{Session, ROOT_UID};
+use crate::types::{Inode, DIR_MODE};
+use crate::users::{self, Group, User, HOME_PATH};
+use crate::utils;
+
+fn valid_user_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit() || c == '-')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn read_new_password() -> Result<String, &'static str> {
+    let password = read_password("New password: ").ok_or("Password not provided")?;
+    if password.is_empty() {
+        return Err("The password can't be empty");
+    }
+    let retyped = read_password("Retype new password: ").ok_or("Password not provided")?;
+    if password != retyped {
+        return Err("Passwords do not match");
+    }
+    Ok(password)
+}
+
+pub fn handle_useradd(commands: Vec<String>, root_inode: &mut Inode, session: &Session) -> Result<(), &'static str> {
+    if commands.len() != 1 {
+        return Err("Invalid number of arguments, type 'help useradd' to see the usage of the command");
+    }
+    if session.uid != ROOT_UID {
+        return Err("Only root can add users");
+    }
+    let name = &commands[0];
+    if !valid_user_name(name) {
+        return Err("Invalid user name");
+    }
+    if users::find_user(root_inode, name).is_some() || users::read_groups(root_inode).iter().any(|group| &group.name == name) {
+        return Err("User already exists");
+    }
+    let home = format!("{}/{}", HOME_PATH, name);
+    let home_parent = root_inode.get_inode_by_path_mut(&utils::normalize_path(HOME_PATH)).ok_or("Directory not found")?;
+    if home_parent.get_inode_by_name(name).is_some() {
+        return Err("Home directory already exists");
+    }
+    let password = read_new_password()?;
+
+    // Every user gets a private group with the same id, and a home directory only it can access
+    let id = users::next_id(root_inode);
+    let user = User {
+        name: name.clone(),
+        uid: id,
+        gid: id,
+        home,
+    };
+    let mut home_directory = Inode::new(DIR_MODE, name.clone(), user.uid, user.gid);
+    home_directory.set_permissions(0o700);
+    root_inode.get_inode_by_path_mut(&utils::normalize_path(HOME_PATH)).unwrap().add_inode(home_directory);
+    root_inode.refresh_sizes(&utils::normalize_path(HOME_PATH));
+
+    let mut user_list = users::read_users(root_inode);
+    user_list.push(user);
+    users::write_users(root_inode, &user_list);
+    let mut groups = users::read_groups(root_inode);
+    groups.push(Group {
+        name: name.clone(),
+        gid: id,
+        members: Vec::new(),
+    });
+    users::write_groups(root_inode, &groups);
+    users::set_password(root_inode, name, &password);
+    Ok(())
+}
+
+pub fn handle_userdel(commands: Vec<String>, root_inode: &mut Inode, session: &Session) -> Result<(), String> {
+    let remove_home = commands.first().map(String::as_str) == Some("-r");
+    let names = if remove_home { &commands[1..] } else { &commands[..] };
+    if names.len() != 1 {
+        return Err("Invalid number of arguments, type 'help userdel' to see the usage of the command".into());
+    }
+    if session.uid != ROOT_UID {
+        return Err("Only root can remove users".into());
+    }
+    let name = &names[0];
+    let user = users::find_user(root_inode, name).ok_or("User not found")?;
+    if user.uid == ROOT_UID {
+        return Err("The root user cannot be removed".into());
+    }
+    if session.is_logged_in(name) {
+        return Err("The user is logged in".into());
+    }
+    if remove_home {
+        // Checked before anything changes, so that the user is kept when the home directory can't be removed
+        let (parent_path, home_name) = utils::split_path(&user.home)?;
+        if let Some(parent_inode) = root_inode.get_inode_by_path(&parent_path) {
+            if let Some(home_directory) = parent_inode.get_inode_by_name(&home_name) {
+                session.check_remove(parent_inode, &home_directory, &user.home)?;
+            }
+        }
+    }
+
+    let user_list: Vec<User> = users::read_users(root_inode).into_iter().filter(|other| &other.name != name).collect();
+    users::write_users(root_inode, &user_list);
+    let groups: Vec<Group> = users::read_groups(root_inode)
+        .into_iter()
+        .filter(|group| !(group.gid == user.gid && &group.name == name))
+        .map(|mut group| {
+            group.members.retain(|member| member != name);
+            group
+        })
+        .collect();
+    users::write_groups(root_inode, &groups);
+    users::remove_password(root_inode, name);
+    quota::set_user_quota(root_inode, name, None);
+
+    if remove_home {
+        let (parent_path, home_name) = utils::split_path(&user.home)?;
+        if let Some(parent_inode) = root_inode.get_inode_by_path_mut(&parent_path) {
+            if let Some(home_directory) = parent_inode.get_inode_by_name(&home_name) {
+                parent_inode.remove_inode(home_directory);
+            }
+        }
+        root_inode.refresh_sizes(&parent_path);
+    }
+    Ok(())
+}
+
+pub fn handle_passwd(commands: Vec<String>, root_inode: &mut Inode, session: &Session) -> Result<(), &'static str> {
+    if commands.len() > 1 {
+        return Err("Invalid number of arguments, type 'help passwd' to see the usage of the command");
+    }
+    let name = commands.first().unwrap_or(&session.user).clone();
+    if name != session.user && session.uid != ROOT_UID {
+        return Err("Only root can change the password of other users");
+    }
+    if users::find_user(root_inode, &name).is_none() {
+        return Err("User not found");
+    }
+    // A first password is set without the current one
+    if session.uid != ROOT_UID && users::has_password(root_inode, &name) {
+        let current = read_password("Current password: ").ok_or("Password not provided")?;
+        if !users::verify_password(root_inode, &name, &current) {
+            return Err("Authentication failure");
+        }
+    }
+    let password = read_new_password()?;
+    users::set_password(root_inode, &name, &password);
+    Ok(())
+}
+
+pub fn handle_login(commands: Vec<String>, root_inode: &mut Inode, session: &mut Session) -> Result<(), &'static str> {
+    if commands.len() != 1 {
+        return Err("Invalid number of arguments, type 'help login' to see the usage of the command");
+    }
+    let password = read_password("Password: ").ok_or("Password not provided")?;
+    let user = match users::find_user(root_inode, &commands[0]) {
+        Some(user) if users::verify_password(root_inode, &user.name, &password) => user,
+        _ => return Err("Login incorrect"),
+    };
+    // Logging in as root is an elevation like sudo, allowed to the same users
+    if user.uid == ROOT_UID && session.uid != ROOT_UID && !session.can_sudo(root_inode) {
+        return Err("Only members of the sudo group can log in as root");
+    }
+    let groups = users::user_groups(root_inode, &user);
+    let previous = std::mem::replace(session, Session::for_user(&user, groups));
+    session.previous = Some(Box::new(previous));
+    Ok(())
+}
+
+pub fn handle_logout(commands: Vec<String>, session: &mut Session) -> Result<(), &'static str> {
+    if !commands.is_empty() {
+        return Err("Invalid number of arguments, type 'help logout' to see the usage of the command");
+    }
+    match session.previous.take() {
+        Some(previous) => {
+            *session = *previous;
+            Ok(())
+        },
+        None => Err("Not logged in as another user, type 'exit' to leave the program"),
+    }
+}
+
+pub fn handle_whoami(commands: Vec<String>, session: &Session, output: &mut dyn Write) -> Result<(), &'static str> {
+    if !commands.is_empty() {
+        return Err("Invalid number of arguments, type 'help whoami' to see the usage of the command");
+    }
+    writeln!(output, "{}", session.user).unwrap();
+    Ok(())
+}
@@ -0,0 +1,610 @@
+use std::collections::HashMap;
+use super::gap_buffer::GapBuffer;
+use super::history::History;
+
+const MAX_COUNT: usize = 9999; // counts are capped so that 99999999p cannot exhaust the memory
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Motion {
+    Left,
+    Right,
+    Down,
+    Up,
+    WordForward,
+    WordBackward,
+    LineStart,
+    LineEnd,
+    FirstLine,
+    LastLine,
+}
+
+impl Motion {
+    fn parse(keys: &str) -> Parse<Motion> {
+        match keys {
+            "h" => Parse::Complete(Motion::Left),
+            "l" => Parse::Complete(Motion::Right),
+            "j" => Parse::Complete(Motion::Down),
+            "k" => Parse::Complete(Motion::Up),
+            "w" => Parse::Complete(Motion::WordForward),
+            "b" => Parse::Complete(Motion::WordBackward),
+            "0" => Parse::Complete(Motion::LineStart),
+            "$" => Parse::Complete(Motion::LineEnd),
+            "gg" => Parse::Complete(Motion::FirstLine),
+            "G" => Parse::Complete(Motion::LastLine),
+            "" | "g" => Parse::Incomplete,
+            _ => Parse::Invalid,
+        }
+    }
+
+    fn linewise(self) -> bool {
+        // Operators on these motions work on whole lines
+        matches!(self, Motion::Down | Motion::Up | Motion::FirstLine | Motion::LastLine)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Operator {
+    Delete,
+    Yank,
+    Change,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Command {
+    Move(Motion),
+    Operate(Operator, Option<Motion>), // without a motion, as in dd, the operator works on count lines
+    DeleteChar,
+    Put { before: bool },
+    Open { above: bool },
+    Append { end_of_line: bool },
+}
+
+#[derive(PartialEq, Debug)]
+pub enum Parse<T> {
+    Incomplete,
+    Invalid,
+    Complete(T),
+}
+
+#[derive(Clone, Default)]
+pub struct Register {
+    // Text yanked or deleted, put back whole lines at a time when it was taken from whole lines
+    pub text: String,
+    pub linewise: bool,
+}
+
+#[derive(Default)]
+pub struct Registers {
+    // Registers of the editor by name, '"' being the unnamed one that every yank and delete also fills.
+    // They belong to the shell session, so that text can be copied from one file into another
+    registers: HashMap<char, Register>,
+}
+
+impl Registers {
+    pub fn get(&self, name: Option<char>) -> Register {
+        let name = name.unwrap_or('"').to_ascii_lowercase();
+        self.registers.get(&name).cloned().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, name: Option<char>, register: Register) {
+        // An uppercase name appends to the register instead of replacing it
+        let register = match name {
+            Some(name) if name.is_ascii_uppercase() => {
+                let mut current = self.get(Some(name));
+                if register.linewise && !current.text.is_empty() && !current.text.ends_with('\n') {
+                    current.text.push('\n');
+                }
+                current.text.push_str(&register.text);
+                current.linewise |= register.linewise;
+                current
+            },
+            _ => register,
+        };
+        if let Some(name) = name.filter(|name| *name != '"') {
+            self.registers.insert(name.to_ascii_lowercase(), register.clone());
+        }
+        self.registers.insert('"', register);
+    }
+}
+
+pub struct Outcome {
+    pub changed: bool,
+    pub insert: bool, // the command ends in insert mode
+}
+
+fn split_count(keys: &str) -> (Option<usize>, &str) {
+    // A leading 0 is the motion to the beginning of the line, not a count
+    let digits = keys.find(|c: char| !c.is_ascii_digit()).unwrap_or(keys.len());
+    if digits == 0 || keys.starts_with('0') {
+        return (None, keys);
+    }
+    (Some(keys[..digits].parse().unwrap_or(MAX_COUNT).min(MAX_COUNT)), &keys[digits..])
+}
+
+pub fn parse_register(keys: &str) -> Parse<(Option<char>, &str)> {
+    // A command can start with "x to use the register x
+    let Some(rest) = keys.strip_prefix('"') else { return Parse::Complete((None, keys)) };
+    match rest.chars().next() {
+        None => Parse::Incomplete,
+        Some('"') => Parse::Complete((None, &rest[1..])),
+        Some(name) if name.is_ascii_alphabetic() => Parse::Complete((Some(name), &rest[1..])),
+        Some(_) => Parse::Invalid,
+    }
+}
+
+pub fn parse(keys: &str) -> Parse<(Option<usize>, Command)> {
+    // Parse the keys typed in command mode: an optional count, then a motion, an operator with its own
+    // optional count and a motion, or another command
+    let (count, rest) = split_count(keys);
+    let mut chars = rest.chars();
+    let Some(first) = chars.next() else { return Parse::Incomplete };
+    let operator = match first {
+        'd' => Some(Operator::Delete),
+        'y' => Some(Operator::Yank),
+        'c' => Some(Operator::Change),
+        _ => None,
+    };
+    if let Some(operator) = operator {
+        let (motion_count, motion_keys) = split_count(chars.as_str());
+        let count = match (count, motion_count) {
+            (Some(count), Some(motion_count)) => Some((count * motion_count).min(MAX_COUNT)),
+            (count, motion_count) => count.or(motion_count),
+        };
+        if motion_keys.len() == 1 && motion_keys.starts_with(first) {
+            return Parse::Complete((count, Command::Operate(operator, None)));
+        }
+        return match Motion::parse(motion_keys) {
+            Parse::Complete(motion) => Parse::Complete((count, Command::Operate(operator, Some(motion)))),
+            Parse::Incomplete => Parse::Incomplete,
+            Parse::Invalid => Parse::Invalid,
+        };
+    }
+    let command = match first {
+        'x' => Command::DeleteChar,
+        'p' => Command::Put { before: false },
+        'P' => Command::Put { before: true },
+        'o' => Command::Open { above: false },
+        'O' => Command::Open { above: true },
+        'a' => Command::Append { end_of_line: false },
+        'A' => Command::Append { end_of_line: true },
+        _ => {
+            return match Motion::parse(rest) {
+                Parse::Complete(motion) => Parse::Complete((count, Command::Move(motion))),
+                Parse::Incomplete => Parse::Incomplete,
+                Parse::Invalid => Parse::Invalid,
+            }
+        },
+    };
+    if !chars.as_str().is_empty() {
+        return Parse::Invalid;
+    }
+    Parse::Complete((count, command))
+}
+
+fn class(c: char) -> u8 {
+    // Words are runs of letters, digits and underscores, or runs of other visible characters
+    if c.is_whitespace() {
+        0
+    } else if c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+fn line_start(data: &GapBuffer, position: usize) -> usize {
+    data.find("\n", position, false).map_or(0, |newline| newline + 1)
+}
+
+fn line_end(data: &GapBuffer, position: usize) -> usize {
+    data.find("\n", position, true).unwrap_or(data.char_count())
+}
+
+fn line_position(data: &GapBuffer, line: usize) -> usize {
+    // Offset of the beginning of the line, or of the last line when there are not so many
+    let mut start = 0;
+    for _ in 0..line {
+        match data.find("\n", start, true) {
+            Some(newline) => start = newline + 1,
+            None => break,
+        }
+    }
+    start
+}
+
+fn word_end(data: &GapBuffer, position: usize) -> usize {
+    match data.get(position).map(class) {
+        Some(0) | None => next_word_start(data, position),
+        Some(current) => {
+            let mut end = position;
+            while data.get(end).is_some_and(|c| class(c) == current) {
+                end += 1;
+            }
+            end
+        },
+    }
+}
+
+fn next_word_start(data: &GapBuffer, position: usize) -> usize {
+    let mut next = position;
+    if let Some(current) = data.get(next).map(class).filter(|current| *current != 0) {
+        while data.get(next).is_some_and(|c| class(c) == current) {
+            next += 1;
+        }
+    }
+    while data.get(next).is_some_and(|c| class(c) == 0) {
+        next += 1;
+    }
+    next
+}
+
+fn previous_word_start(data: &GapBuffer, position: usize) -> usize {
+    let mut previous = position;
+    while previous > 0 && data.get(previous - 1).is_some_and(|c| class(c) == 0) {
+        previous -= 1;
+    }
+    let Some(current) = previous.checked_sub(1).and_then(|index| data.get(index)).map(class) else { return previous };
+    while previous > 0 && data.get(previous - 1).is_some_and(|c| class(c) == current) {
+        previous -= 1;
+    }
+    previous
+}
+
+fn target(data: &mut GapBuffer, motion: Motion, count: Option<usize>) -> usize {
+    // Offset the motion leads to from the cursor, which is left where it is
+    let position = data.position();
+    let times = count.unwrap_or(1);
+    match motion {
+        Motion::Left => (0..times).fold(position, |position, _| data.previous_grapheme(position)),
+        Motion::Right => (0..times).fold(position, |position, _| data.next_grapheme(position)),
+        Motion::Down | Motion::Up => {
+            for _ in 0..times {
+                if motion == Motion::Down {
+                    data.move_down();
+                } else {
+                    data.move_up();
+                }
+            }
+            let target = data.position();
+            data.set_position(position);
+            target
+        },
+        Motion::WordForward => (0..times).fold(position, |position, _| next_word_start(data, position)),
+        Motion::WordBackward => (0..times).fold(position, |position, _| previous_word_start(data, position)),
+        Motion::LineStart => line_start(data, position),
+        Motion::LineEnd => (1..times).fold(line_end(data, position), |end, _| {
+            if end < data.char_count() {
+                line_end(data, end + 1)
+            } else {
+                end
+            }
+        }),
+        Motion::FirstLine => line_position(data, count.map_or(0, |line| line - 1)),
+        Motion::LastLine => line_position(data, count.map_or(data.line_count() - 1, |line| line - 1)),
+    }
+}
+
+fn insert(data: &mut GapBuffer, history: &mut History, at: usize, text: &str) {
+    data.set_position(at);
+    data.push_str(text);
+    history.record_insert(at, text);
+}
+
+fn delete(data: &mut GapBuffer, history: &mut History, start: usize, end: usize) {
+    let cursor_before = data.position();
+    data.set_position(start);
+    let removed = data.delete_forward(end - start);
+    if !removed.is_empty() {
+        history.record_delete(start, &removed, cursor_before);
+    }
+}
+
+fn apply(operator: Operator, (start, end): (usize, usize), linewise: bool, data: &mut GapBuffer, history: &mut History, registers: &mut Registers, name: Option<char>) -> Outcome {
+    // Apply the operator from start to end, the end of a range of lines being the end of the last one before its newline
+    let length = data.char_count();
+    let text = data.slice(start, end);
+    registers.set(name, Register {
+        text: if linewise { text + "\n" } else { text },
+        linewise,
+    });
+    match operator {
+        Operator::Yank => {
+            if !linewise {
+                data.set_position(start);
+            }
+            Outcome { changed: false, insert: false }
+        },
+        Operator::Delete if linewise => {
+            // The newline after the lines goes with them, or the one before the last line of the text
+            if end < length {
+                delete(data, history, start, end + 1);
+            } else {
+                delete(data, history, start.saturating_sub(1), end);
+            }
+            let position = data.position();
+            data.set_position(line_start(data, position));
+            Outcome { changed: true, insert: false }
+        },
+        Operator::Delete | Operator::Change => {
+            delete(data, history, start, end);
+            Outcome {
+                changed: start != end,
+                insert: operator == Operator::Change,
+            }
+        },
+    }
+}
+
+fn operate(operator: Operator, motion: Option<Motion>, count: Option<usize>, data: &mut GapBuffer, history: &mut History, registers: &mut Registers, name: Option<char>) -> Outcome {
+    let position = data.position();
+    let length = data.char_count();
+    let (start, end, linewise) = match motion {
+        None => {
+            let last = (1..count.unwrap_or(1)).fold(position, |line, _| {
+                let end = line_end(data, line);
+                if end < length {
+                    end + 1
+                } else {
+                    line
+                }
+            });
+            (line_start(data, position), line_end(data, last), true)
+        },
+        Some(motion) if motion.linewise() => {
+            let target = target(data, motion, count);
+            (line_start(data, position.min(target)), line_end(data, position.max(target)), true)
+        },
+        // cw changes the word up to its end, leaving the blank after it, as in vi
+        Some(Motion::WordForward) if operator == Operator::Change && data.get(position).is_some_and(|c| class(c) != 0) => {
+            let end = (1..count.unwrap_or(1)).fold(word_end(data, position), |end, _| word_end(data, next_word_start(data, end)));
+            (position, end, false)
+        },
+        // When the last word moved over ends its line, the operator stops there instead of taking the newline, as in vi
+        Some(Motion::WordForward) => {
+            let target = target(data, Motion::WordForward, count);
+            let start = line_start(data, target);
+            let blank = (start..target).all(|index| data.get(index).is_some_and(char::is_whitespace));
+            let end = if start > position && blank { start - 1 } else { target };
+            (position, end, false)
+        },
+        Some(motion) => {
+            let target = target(data, motion, count);
+            (position.min(target), position.max(target), false)
+        },
+    };
+    apply(operator, (start, end), linewise, data, history, registers, name)
+}
+
+fn put(register: &Register, before: bool, times: usize, data: &mut GapBuffer, history: &mut History) -> bool {
+    // Insert the text of the register count times after the cursor, or after the line with whole lines
+    let text = register.text.repeat(times);
+    if text.is_empty() {
+        return false;
+    }
+    let position = data.position();
+    let end = line_end(data, position);
+    if !register.linewise {
+        let at = if before { position } else { (position + 1).min(end) };
+        insert(data, history, at, &text);
+        let end = at + text.chars().count();
+        data.set_position(data.previous_grapheme(end));
+    } else if before {
+        let at = line_start(data, position);
+        insert(data, history, at, &text);
+        data.set_position(at);
+    } else if end < data.char_count() {
+        insert(data, history, end + 1, &text);
+        data.set_position(end + 1);
+    } else {
+        // After the last line, which has no newline to put the lines after
+        insert(data, history, end, &format!("\n{}", text.strip_suffix('\n').unwrap_or(&text)));
+        data.set_position(end + 1);
+    }
+    true
+}
+
+fn selection_range(data: &GapBuffer, anchor: usize, linewise: bool) -> (usize, usize) {
+    // The selection goes from the anchor to the cursor, both included
+    let position = data.position();
+    let (first, last) = (anchor.min(position), anchor.max(position));
+    if linewise {
+        (line_start(data, first), line_end(data, last))
+    } else {
+        (first, data.next_grapheme(last).max(last + 1).min(data.char_count()))
+    }
+}
+
+pub fn operate_selection(operator: Operator, anchor: usize, linewise: bool, data: &mut GapBuffer, history: &mut History, registers: &mut Registers, name: Option<char>) -> Outcome {
+    let (start, end) = selection_range(data, anchor, linewise);
+    apply(operator, (start, end), linewise, data, history, registers, name)
+}
+
+pub fn put_over_selection(anchor: usize, linewise: bool, data: &mut GapBuffer, history: &mut History, registers: &mut Registers, name: Option<char>) -> Outcome {
+    // Replace the selection with the register, the selected text going to the unnamed register
+    let register = registers.get(name);
+    let (start, end) = selection_range(data, anchor, linewise);
+    let selected = data.slice(start, end);
+    if linewise {
+        delete(data, history, start, end);
+        if register.linewise {
+            // The newline of the last selected line is still there for the lines put in place
+            insert(data, history, start, register.text.strip_suffix('\n').unwrap_or(&register.text));
+        } else {
+            insert(data, history, start, &register.text);
+        }
+        data.set_position(start);
+    } else {
+        delete(data, history, start, end);
+        put(&register, true, 1, data, history);
+    }
+    registers.set(None, Register {
+        text: if linewise { selected + "\n" } else { selected },
+        linewise,
+    });
+    Outcome { changed: true, insert: false }
+}
+
+pub fn execute(command: Command, count: Option<usize>, data: &mut GapBuffer, history: &mut History, registers: &mut Registers, name: Option<char>) -> Outcome {
+    // Run a command parsed from the keys typed in command mode, with the register named before it
+    let position = data.position();
+    let times = count.unwrap_or(1);
+    let mut outcome = Outcome { changed: false, insert: false };
+    match command {
+        Command::Move(motion) => {
+            let target = target(data, motion, count);
+            data.set_position(target);
+        },
+        Command::Operate(operator, motion) => outcome = operate(operator, motion, count, data, history, registers, name),
+        Command::DeleteChar => {
+            let end = (0..times).fold(position, |end, _| data.next_grapheme(end));
+            if end > position {
+                outcome = apply(Operator::Delete, (position, end), false, data, history, registers, name);
+            }
+        },
+        Command::Put { before } => outcome.changed = put(&registers.get(name), before, times, data, history),
+        Command::Open { above } => {
+            if above {
+                let at = line_start(data, position);
+                insert(data, history, at, "\n");
+                data.set_position(at);
+            } else {
+                insert(data, history, line_end(data, position), "\n");
+            }
+            outcome = Outcome { changed: true, insert: true };
+        },
+        Command::Append { end_of_line } => {
+            let end = line_end(data, position);
+            data.set_position(if end_of_line { end } else { (position + 1).min(end) });
+            outcome.insert = true;
+        },
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{execute, operate_selection, parse, parse_register, put_over_selection, Command, Motion, Operator, Parse, Register, Registers};
+    use crate::commands::gap_buffer::GapBuffer;
+    use crate::commands::history::History;
+
+    fn run_with(registers: &mut Registers, text: &str, position: usize, keys: &[&str]) -> (String, usize) {
+        let mut data = GapBuffer::from_string(text);
+        let mut history = History::new();
+        data.set_position(position);
+        for keys in keys {
+            let Parse::Complete((name, keys)) = parse_register(keys) else { panic!("invalid register in {}", keys) };
+            let Parse::Complete((count, command)) = parse(keys) else { panic!("invalid keys {}", keys) };
+            execute(command, count, &mut data, &mut history, registers, name);
+        }
+        (data.to_string(), data.position())
+    }
+
+    fn run(text: &str, position: usize, keys: &[&str]) -> (String, usize, Register) {
+        let mut registers = Registers::default();
+        let (text, position) = run_with(&mut registers, text, position, keys);
+        (text, position, registers.get(None))
+    }
+
+    #[test]
+    fn keys_are_parsed_with_counts() {
+        assert_eq!(parse("2"), Parse::Incomplete);
+        assert_eq!(parse("d"), Parse::Incomplete);
+        assert_eq!(parse("g"), Parse::Incomplete);
+        assert_eq!(parse("0"), Parse::Complete((None, Command::Move(Motion::LineStart))));
+        assert_eq!(parse("10j"), Parse::Complete((Some(10), Command::Move(Motion::Down))));
+        assert_eq!(parse("2d3w"), Parse::Complete((Some(6), Command::Operate(Operator::Delete, Some(Motion::WordForward)))));
+        assert_eq!(parse("yy"), Parse::Complete((None, Command::Operate(Operator::Yank, None))));
+        assert_eq!(parse("dgg"), Parse::Complete((None, Command::Operate(Operator::Delete, Some(Motion::FirstLine)))));
+        assert_eq!(parse("dy"), Parse::Invalid);
+        assert_eq!(parse("z"), Parse::Invalid);
+    }
+
+    #[test]
+    fn motions_move_the_cursor() {
+        let text = "one two.three\nfour";
+        assert_eq!(run(text, 0, &["w"]).1, 4);
+        assert_eq!(run(text, 0, &["3w"]).1, 8);
+        assert_eq!(run(text, 0, &["4w"]).1, 14);
+        assert_eq!(run(text, 14, &["b"]).1, 8);
+        assert_eq!(run(text, 5, &["$"]).1, 13);
+        assert_eq!(run(text, 5, &["0"]).1, 0);
+        assert_eq!(run(text, 5, &["l", "10h"]).1, 0);
+        assert_eq!(run(text, 5, &["G"]).1, 14);
+        assert_eq!(run(text, 16, &["gg"]).1, 0);
+    }
+
+    #[test]
+    fn operators_fill_the_register() {
+        let (text, _, register) = run("one two three", 0, &["dw"]);
+        assert_eq!(text, "two three");
+        assert_eq!(register.text, "one ");
+        let (text, position, _) = run("one two three", 4, &["cw"]);
+        assert_eq!((text.as_str(), position), ("one  three", 4));
+        let (text, _, register) = run("one\ntwo", 0, &["dw"]);
+        assert_eq!((text.as_str(), register.text.as_str()), ("\ntwo", "one"));
+        assert_eq!(run("one two\nthree", 0, &["d2w"]).0, "\nthree");
+        assert_eq!(run("one\ntwo three", 0, &["d2w"]).0, "three");
+        assert_eq!(run("one\n  two", 0, &["dw"]).0, "\n  two");
+        let (text, _, register) = run("one two", 4, &["y$"]);
+        assert_eq!((text.as_str(), register.text.as_str()), ("one two", "two"));
+    }
+
+    #[test]
+    fn lines_are_deleted_and_put_back() {
+        let (text, position, register) = run("a\nb\nc", 2, &["dd"]);
+        assert_eq!((text.as_str(), position), ("a\nc", 2));
+        assert!(register.linewise);
+        assert_eq!(run("a\nb\nc", 2, &["dd", "p"]).0, "a\nc\nb");
+        assert_eq!(run("a\nb\nc", 4, &["dd"]).0, "a\nb");
+        assert_eq!(run("a\nb\nc", 4, &["yy", "P"]).0, "a\nb\nc\nc");
+        assert_eq!(run("a\nb\nc", 0, &["2dd"]).0, "c");
+        assert_eq!(run("a\nb\nc", 0, &["dj"]).0, "c");
+        assert_eq!(run("a\nb\nc", 2, &["cc"]).0, "a\n\nc");
+    }
+
+    #[test]
+    fn characters_are_deleted_and_put_back() {
+        let (text, position, _) = run("abc", 0, &["x", "p"]);
+        assert_eq!((text.as_str(), position), ("bac", 1));
+        assert_eq!(run("abc", 1, &["5x"]).0, "a");
+        assert_eq!(run("abc\nd", 0, &["o"]).0, "abc\n\nd");
+        assert_eq!(run("abc\nd", 4, &["O"]).0, "abc\n\nd");
+    }
+
+    #[test]
+    fn named_registers_are_kept_apart() {
+        let mut registers = Registers::default();
+        assert_eq!(parse_register("\""), Parse::Incomplete);
+        assert_eq!(parse_register("\"a2dd"), Parse::Complete((Some('a'), "2dd")));
+        assert_eq!(parse_register("\"1p"), Parse::Invalid);
+        run_with(&mut registers, "one two", 0, &["\"ayw", "w", "yw"]);
+        assert_eq!(registers.get(Some('a')).text, "one ");
+        assert_eq!(registers.get(None).text, "two");
+        // Another file gets the text of the registers
+        assert_eq!(run_with(&mut registers, "x", 0, &["\"ap"]).0, "xone ");
+        run_with(&mut registers, "three\nfour", 0, &["\"Ayy"]);
+        let register = registers.get(Some('a'));
+        assert_eq!((register.text.as_str(), register.linewise), ("one \nthree\n", true));
+    }
+
+    #[test]
+    fn selections_are_yanked_cut_and_replaced() {
+        let mut registers = Registers::default();
+        let mut history = History::new();
+        let mut data = GapBuffer::from_string("one two\nthree");
+        data.set_position(6);
+        operate_selection(Operator::Yank, 4, false, &mut data, &mut history, &mut registers, Some('b'));
+        assert_eq!(registers.get(Some('b')).text, "two");
+        assert_eq!(data.position(), 4);
+        data.set_position(8);
+        operate_selection(Operator::Delete, 0, true, &mut data, &mut history, &mut registers, None);
+        assert_eq!(data.to_string(), "");
+        assert_eq!(registers.get(None).text, "one two\nthree\n");
+
+        let mut data = GapBuffer::from_string("a b c");
+        data.set_position(2);
+        put_over_selection(2, false, &mut data, &mut history, &mut registers, Some('b'));
+        assert_eq!(data.to_string(), "a two c");
+        assert_eq!(registers.get(None).text, "b");
+    }
+}
@@ -1,112 +1,770 @@
-use crate::commands::create_new_file;
-use crate::types::{DIR_MODE, Inode};
+use std::io::{Read, Write};
+use crate::commands::{edit_file, Files, handle_audit, handle_chgrp, handle_chmod, handle_chown, handle_decrypt, handle_encrypt, handle_getfacl, handle_immutable, handle_login, handle_readonly, handle_setfacl, handle_logout, handle_passwd, handle_quota, handle_setquota, handle_useradd, handle_userdel, handle_whoami};
+use crate::audit;
+use crate::line_editor::read_password;
+use crate::quota::{self, Usage};
+use crate::session::{Session, ROOT_UID};
+use crate::types::{BLOCK_SIZE, DIR_MODE, EXECUTE, Inode, READ, STICKY, WRITE};
+use crate::users;
+use crate::utils;
 
-pub fn handle_commands(commands: Vec<String>, actual_inode: &mut Inode) -> bool {
+pub const COMMANDS: &[&str] = &["help", "new", "edit", "remove", "rename", "move", "ls", "du", "cat", "grep", "wc", "echo", "touch", "write", "useradd", "userdel", "passwd", "login", "logout", "whoami", "sudo", "audit", "chmod", "chown", "chgrp", "readonly", "immutable", "getfacl", "setfacl", "encrypt", "decrypt", "quota", "setquota", "exit"];
+
+pub fn handle_commands(commands: Vec<String>, actual_inode: &mut Inode, session: &mut Session, input: &mut dyn Read, output: &mut dyn Write) -> bool {
+    // Run the command, printing its error if any. Only exit quits the program
+    if commands[0] == "exit" {
+        return true;
+    }
+    if let Err(error) = run_command(commands, actual_inode, session, input, output) {
+        eprintln!("{}", error);
+    }
+    print_warnings(session);
+    false
+}
+
+pub fn print_warnings(session: &mut Session) {
+    // Warnings left by the command, printed once it is done so that they don't go over the editor
+    for warning in session.warnings.drain(..) {
+        eprintln!("{}", warning);
+    }
+}
+
+fn run_command(commands: Vec<String>, actual_inode: &mut Inode, session: &mut Session, input: &mut dyn Read, output: &mut dyn Write) -> Result<(), String> {
+    let user = session.user.clone();
+    audit::audited(actual_inode, &user, &commands.clone(), |actual_inode| dispatch(commands, actual_inode, session, input, output))
+}
+
+fn dispatch(commands: Vec<String>, actual_inode: &mut Inode, session: &mut Session, input: &mut dyn Read, output: &mut dyn Write) -> Result<(), String> {
+    let arguments = commands[1..].to_vec();
     match commands[0].as_str() {
         "help" => {
-            let help_commands = commands[1..].to_vec();
-            handle_help(help_commands);
-            false
-
-        }
-        "new" => {
-            let new_commands = commands[1..].to_vec();
-            match handle_new(new_commands, actual_inode) {
-                Ok(()) => {
-                    false
-                },
-                Err(error) => {
-                    println!("{}", error);
-                    false
-                }
-            }
+            handle_help(arguments, output);
+            Ok(())
         }
-        "remove" => {
-            let new_commands = commands[1..].to_vec();
-            match handle_remove(new_commands, actual_inode) {
-                Ok(()) => {
-                    false
-                },
-                Err(error) => {
-                    println!("{}", error);
-                    false
-                }
-            }
+        "new" => handle_new(arguments, actual_inode, session),
+        "edit" => handle_edit(arguments, actual_inode, session),
+        "remove" => handle_remove(arguments, actual_inode, session),
+        "rename" => handle_rename(arguments, actual_inode, session),
+        "move" => handle_move(arguments, actual_inode, session),
+        "ls" => Ok(handle_ls(arguments, actual_inode, session, output)?),
+        "du" => Ok(handle_du(arguments, actual_inode, session, output)?),
+        "cat" => handle_cat(arguments, actual_inode, session, input, output),
+        "grep" => handle_grep(arguments, actual_inode, session, input, output),
+        "wc" => handle_wc(arguments, actual_inode, session, input, output),
+        "echo" => Ok(handle_echo(arguments, output)?),
+        "touch" => handle_touch(arguments, actual_inode, session),
+        "write" => handle_write(arguments, actual_inode, session),
+        "useradd" => Ok(handle_useradd(arguments, actual_inode, session)?),
+        "userdel" => handle_userdel(arguments, actual_inode, session),
+        "passwd" => Ok(handle_passwd(arguments, actual_inode, session)?),
+        "login" => Ok(handle_login(arguments, actual_inode, session)?),
+        "logout" => Ok(handle_logout(arguments, session)?),
+        "whoami" => Ok(handle_whoami(arguments, session, output)?),
+        "sudo" => handle_sudo(arguments, actual_inode, session, input, output),
+        "audit" => handle_audit(arguments, actual_inode, session, output),
+        "chmod" => handle_chmod(arguments, actual_inode, session),
+        "chown" => handle_chown(arguments, actual_inode, session),
+        "chgrp" => handle_chgrp(arguments, actual_inode, session),
+        "readonly" => handle_readonly(arguments, actual_inode, session),
+        "immutable" => handle_immutable(arguments, actual_inode, session),
+        "getfacl" => handle_getfacl(arguments, actual_inode, session, output),
+        "setfacl" => handle_setfacl(arguments, actual_inode, session),
+        "encrypt" => handle_encrypt(arguments, actual_inode, session),
+        "decrypt" => handle_decrypt(arguments, actual_inode, session),
+        "quota" => handle_quota(arguments, actual_inode, session, output),
+        "setquota" => handle_setquota(arguments, actual_inode, session),
+        _ => Err("Command not found. Type 'help' to see the list of available commands".into()),
+    }
+}
+
+fn handle_sudo(commands: Vec<String>, root_inode: &mut Inode, session: &mut Session, input: &mut dyn Read, output: &mut dyn Write) -> Result<(), String> {
+    // Run one command as root, after the user of the session proves its identity. Every attempt is audited
+    // by run_command, and the command itself is recorded again as run by root
+    if commands.is_empty() {
+        return Err("Invalid number of arguments, type 'help sudo' to see the usage of the command".into());
+    }
+    if matches!(commands[0].as_str(), "sudo" | "login" | "logout" | "exit") {
+        return Err(format!("'{}' can't be run with sudo", commands[0]));
+    }
+    if session.uid != ROOT_UID {
+        if !session.can_sudo(root_inode) {
+            return Err(format!("'{}' is not allowed to run commands as root", session.user));
         }
-        "exit" => true,
-        _ => {
-            println!("Command not found. Type 'help' to see the list of available commands");
-            false
+        if !users::has_password(root_inode, &session.user) {
+            return Err("Set a password with passwd before using sudo".into());
+        }
+        let password = read_password(&format!("[sudo] password for {}: ", session.user)).ok_or("Password not provided")?;
+        if !users::verify_password(root_inode, &session.user, &password) {
+            return Err("Authentication failure".into());
         }
     }
+
+    // The elevated session keeps the passphrase of the user, so its encrypted files stay readable,
+    // and the registers of the editor
+    let mut elevated = Session::root();
+    elevated.passphrase = session.passphrase.take();
+    elevated.registers = std::mem::take(&mut session.registers);
+    let result = run_command(commands, root_inode, &mut elevated, input, output);
+    session.passphrase = elevated.passphrase.take();
+    session.registers = std::mem::take(&mut elevated.registers);
+    session.warnings.append(&mut elevated.warnings);
+    result
 }
 
-fn handle_help(commands: Vec<String>) {
+fn handle_help(commands: Vec<String>, output: &mut dyn Write) {
     let help_command = String::from("— help: \t\t\t\tshow the list of available commands");
-    let new_command = String::from("— new <file|directory> <name>: \tcreate a new file or directory");
-    let remove_command = String::from("— remove <filename|directoryname>: \tif a file is provided, remove the file.
+    let new_command = String::from("— new <file|directory> <path>: \tcreate a new file or directory");
+    let edit_command = String::from("— edit <path>: \t\t\topen an existing file in the editor.
+                \t\t\t\tin the editor, :e <path> opens another file, :bn, :bp and :ls go through the open files,
+                \t\t\t\t:sp and :vs split the window and Ctrl+W moves to the other window");
+    let remove_command = String::from("— remove <path>: \t\t\tif a file is provided, remove the file.
                 \t\t\t\tif a directory is provided, recursively remove the directory and all its content");
+    let rename_command = String::from("— rename <path> <name>: \t\tgive a new name to the file or directory");
+    let move_command = String::from("— move <path> <directory>: \t\tmove the file or directory into another directory");
+    let ls_command = String::from("— ls [-l] [path]: \t\t\tlist the content of the directory (the current one by default).
+                \t\t\t\twith -l, also show the permissions, owner, group and size of each entry");
+    let du_command = String::from("— du [-s] [-h] [path]: \t\tshow the allocated blocks and the size in bytes of the directory and of each
+                \t\t\t\tsubdirectory (the current one by default). with -s, only of the directory. with -h, in K, M or G");
+    let cat_command = String::from("— cat [path]: \t\t\tprint the content of the file, or of the input when no file is provided");
+    let grep_command = String::from("— grep <pattern> [path]: \t\tprint the lines of the file (or of the input) containing the pattern");
+    let wc_command = String::from("— wc [-l|-w|-c] [path]: \t\tcount the lines, words and bytes of the file (or of the input)");
+    let echo_command = String::from("— echo [-n] <text>: \t\t\tprint the text, followed by a new line unless -n is provided");
+    let touch_command = String::from("— touch <path>: \t\t\tcreate an empty file, or update the timestamps of an existing one");
+    let write_command = String::from("— write <path> <text>: \t\treplace the content of the file with the text, creating it if needed");
+    let useradd_command = String::from("— useradd <name>: \t\t\t(root only) create a user, with its own group and home directory");
+    let userdel_command = String::from("— userdel [-r] <name>: \t\t(root only) remove a user. with -r, also remove its home directory");
+    let passwd_command = String::from("— passwd [name]: \t\t\tchange your password, or the password of another user (root only)");
+    let login_command = String::from("— login <name>: \t\t\tlog in as another user, until logout.
+                \t\t\t\tlogging in as root is limited to the sudo group, and recorded in /var/log/audit");
+    let logout_command = String::from("— logout: \t\t\t\tgo back to the user logged in before the last login");
+    let whoami_command = String::from("— whoami: \t\t\t\tprint the name of the current user");
+    let sudo_command = String::from("— sudo <command>: \t\t\trun the command as root, after asking your password.
+                \t\t\t\tonly members of the sudo group can use it, and every use is recorded in /var/log/audit");
+    let audit_command = String::from("— audit [--since <time>] [--path <path>]: \tprint the log of changes to the filesystem and of sudo uses,
+                \t\t\t\tsince a timestamp or a duration like 2h, or about a path and its content (root only)");
+    let chmod_command = String::from("— chmod [-R] <mode> <path>: \t\tchange the permissions, in octal (like 755) or symbolic (like u+x,go-w) form");
+    let chown_command = String::from("— chown [-R] <user>[:group] <path>: \tchange the owner, and optionally the group, of the file or directory.
+                \t\t\t\tonly root can change the owner");
+    let chgrp_command = String::from("— chgrp [-R] <group> <path>: \t\tchange the group of the file or directory.
+                \t\t\t\tonly the owner or root can change them, and -R applies the change to all the content");
+    let readonly_command = String::from("— readonly <on|off> <path>: \t\t(owner or root) protect the file or directory from being changed, renamed or removed");
+    let immutable_command = String::from("— immutable <on|off> <path>: \t(root only) same as readonly, also preventing changes of permissions and owner");
+    let getfacl_command = String::from("— getfacl <path>: \t\t\tshow the access control list of the file or directory");
+    let setfacl_command = String::from("— setfacl [-R] [-d] [-b] [-k] [-m|-x <entries>] <path>: change the access control list.
+                \t\t\t\tentries are like u:name:rwx,g:name:r-x,m::rwx (-m) or u:name,g:name (-x).
+                \t\t\t\t-d changes the default list of named entries inherited by new content, -b removes all entries
+                \t\t\t\tand -k removes the default list. only the owner or root can change them");
+    let encrypt_command = String::from("— encrypt <path>: \t\t\tencrypt the content of the file with a passphrase, asked twice.
+                \t\t\t\tencrypted files are decrypted on the fly when read with the same passphrase,
+                \t\t\t\twhich the session then keeps");
+    let decrypt_command = String::from("— decrypt <path>: \t\t\tstore the content of the encrypted file in clear again");
+    let quota_command = String::from("— quota [name]: \t\t\tshow the disk usage and quotas of the user (yourself by default),
+                \t\t\t\tand of the directories with a quota");
+    let setquota_command = String::from("— setquota [-d] <name|path> <soft bytes> <hard bytes> <soft inodes> <hard inodes>:
+                \t\t\t\t(root only) limit what the user owns, or with -d what the directory contains.
+                \t\t\t\tsizes accept K, M and G, 0 means no limit and 'off' instead of the limits removes the quota");
     let exit_command = String::from("— exit: \t\t\t\texit the program");
+    let redirect_command = String::from("— <command> > <path>: \t\twrite the output of the command into a file.
+                \t\t\t\tuse '>>' to append to the file instead of replacing its content");
+    let pipe_command = String::from("— <command> | <command>: \t\tuse the output of the first command as the input of the second one");
     if commands.is_empty() {
-        println!(
+        writeln!(
+            output,
             "Command options:
             {help_command}
             {new_command}
+            {edit_command}
             {remove_command}
+            {rename_command}
+            {move_command}
+            {ls_command}
+            {du_command}
+            {cat_command}
+            {grep_command}
+            {wc_command}
+            {echo_command}
+            {touch_command}
+            {write_command}
+            {useradd_command}
+            {userdel_command}
+            {passwd_command}
+            {login_command}
+            {logout_command}
+            {whoami_command}
+            {sudo_command}
+            {audit_command}
+            {chmod_command}
+            {chown_command}
+            {chgrp_command}
+            {readonly_command}
+            {immutable_command}
+            {getfacl_command}
+            {setfacl_command}
+            {encrypt_command}
+            {decrypt_command}
+            {quota_command}
+            {setquota_command}
             {exit_command}
+            {redirect_command}
+            {pipe_command}
             "
-        );
+        ).unwrap();
     } else if commands.len() > 1 {
-        println!("Too many arguments for help command")
+        writeln!(output, "Too many arguments for help command").unwrap();
     } else {
         match commands[0].as_str() {
-            "new" => println!("{new_command}"),
-            "remove" => println!("{remove_command}"),
-            _ => println!("Command not found. Type 'help' to see the list of available commands"),
+            "new" => writeln!(output, "{new_command}").unwrap(),
+            "edit" => writeln!(output, "{edit_command}").unwrap(),
+            "remove" => writeln!(output, "{remove_command}").unwrap(),
+            "rename" => writeln!(output, "{rename_command}").unwrap(),
+            "move" => writeln!(output, "{move_command}").unwrap(),
+            "ls" => writeln!(output, "{ls_command}").unwrap(),
+            "du" => writeln!(output, "{du_command}").unwrap(),
+            "cat" => writeln!(output, "{cat_command}").unwrap(),
+            "grep" => writeln!(output, "{grep_command}").unwrap(),
+            "wc" => writeln!(output, "{wc_command}").unwrap(),
+            "echo" => writeln!(output, "{echo_command}").unwrap(),
+            "touch" => writeln!(output, "{touch_command}").unwrap(),
+            "write" => writeln!(output, "{write_command}").unwrap(),
+            "useradd" => writeln!(output, "{useradd_command}").unwrap(),
+            "userdel" => writeln!(output, "{userdel_command}").unwrap(),
+            "passwd" => writeln!(output, "{passwd_command}").unwrap(),
+            "login" => writeln!(output, "{login_command}").unwrap(),
+            "logout" => writeln!(output, "{logout_command}").unwrap(),
+            "whoami" => writeln!(output, "{whoami_command}").unwrap(),
+            "sudo" => writeln!(output, "{sudo_command}").unwrap(),
+            "audit" => writeln!(output, "{audit_command}").unwrap(),
+            "chmod" => writeln!(output, "{chmod_command}").unwrap(),
+            "chown" => writeln!(output, "{chown_command}").unwrap(),
+            "chgrp" => writeln!(output, "{chgrp_command}").unwrap(),
+            "readonly" => writeln!(output, "{readonly_command}").unwrap(),
+            "immutable" => writeln!(output, "{immutable_command}").unwrap(),
+            "getfacl" => writeln!(output, "{getfacl_command}").unwrap(),
+            "setfacl" => writeln!(output, "{setfacl_command}").unwrap(),
+            "encrypt" => writeln!(output, "{encrypt_command}").unwrap(),
+            "decrypt" => writeln!(output, "{decrypt_command}").unwrap(),
+            "quota" => writeln!(output, "{quota_command}").unwrap(),
+            "setquota" => writeln!(output, "{setquota_command}").unwrap(),
+            ">" | ">>" => writeln!(output, "{redirect_command}").unwrap(),
+            "|" => writeln!(output, "{pipe_command}").unwrap(),
+            _ => writeln!(output, "Command not found. Type 'help' to see the list of available commands").unwrap(),
         }
     }
 }
 
-fn handle_new(commands: Vec<String>, parent_inode: &mut Inode) -> Result<(), &'static str> {
+fn handle_new(commands: Vec<String>, root_inode: &mut Inode, session: &mut Session) -> Result<(), String> {
     if commands.len() != 2 {
-        return Err("Invalid number of arguments, type 'help new' to see the usage of the command");
+        return Err("Invalid number of arguments, type 'help new' to see the usage of the command".into());
+    }
+    let (parent_path, _) = utils::split_path(&commands[1])?;
+    let quota = quota::check(root_inode, session.uid, &parent_path, Usage { bytes: 0, inodes: 1 });
+    let (parent_inode, name) = session.resolve_parent_mut(root_inode, &commands[1])?;
+    if parent_inode.get_inode_by_name(&name).is_some() {
+        return Err("A file or directory with this name already exists".into());
     }
+    session.check_create(parent_inode, &utils::parent_path(&commands[1]))?;
     if commands[0] == "file" {
-        if commands[1].is_empty() || commands[1].contains("/") || commands[1].contains("\\") {
-            return Err("Invalid name for new file");
-        }
-        match create_new_file(commands[1].clone(), parent_inode) {
-            Ok(()) => {
-                println!("Directory Meta-data: {:#?}", parent_inode);
-                Ok(())
-            },
-            Err(error) => Err(error),
-        }
+        // The file is created when the editor saves it, quitting without saving leaves nothing behind,
+        // and saving checks the quotas again
+        quota?;
+        let mut registers = std::mem::take(&mut session.registers);
+        let result = edit_file(&commands[1], "", &mut SessionFiles { session, root: root_inode }, &mut registers);
+        session.registers = registers;
+        result
     } else if commands[0] == "directory" {
-        if commands[1].is_empty() || commands[1].contains("/") || commands[1].contains("\\") {
-            return Err("Invalid name for new directory");
-        }
-        let new_directory = Inode::new(DIR_MODE, commands[1].clone());
+        session.warnings.extend(quota?);
+        let new_directory = Inode::new(DIR_MODE, name, session.uid, session.gid);
         parent_inode.add_inode(new_directory);
-        println!("Parent directory Meta-data: {:#?}", parent_inode);
-
+        root_inode.refresh_sizes(&parent_path);
         Ok(())
     } else {
-        return Err("Invalid type of new content, type 'help new' to see the usage of the command");
+        Err("Invalid type of new content, type 'help new' to see the usage of the command".into())
     }
 }
 
-fn handle_remove(commands: Vec<String>, parent_inode: &mut Inode) -> Result<(), &'static str> {
+fn handle_remove(commands: Vec<String>, root_inode: &mut Inode, session: &Session) -> Result<(), String> {
     if commands.len() != 1 {
-        return Err("Invalid number of arguments, type 'help remove' to see the usage of the command");
+        return Err("Invalid number of arguments, type 'help remove' to see the usage of the command".into());
     }
-    let inode_to_remove = parent_inode.get_inode_by_name(&commands[0]);
+    let (parent_path, _) = utils::split_path(&commands[0])?;
+    let (parent_inode, name) = session.resolve_parent_mut(root_inode, &commands[0])?;
+    let inode_to_remove = parent_inode.get_inode_by_name(&name);
     match inode_to_remove {
         Some(inode) => {
+            session.check_remove(parent_inode, &inode, &commands[0])?;
             parent_inode.remove_inode(inode);
-            println!("Parent directory Meta-data: {:#?}", parent_inode);
+            root_inode.refresh_sizes(&parent_path);
             Ok(())
         },
-        None => Err("File or directory not found"),
+        None => Err("File or directory not found".into()),
+    }
+}
+
+fn handle_rename(commands: Vec<String>, root_inode: &mut Inode, session: &Session) -> Result<(), String> {
+    if commands.len() != 2 {
+        return Err("Invalid number of arguments, type 'help rename' to see the usage of the command".into());
+    }
+    let new_name = &commands[1];
+    if new_name.is_empty() || new_name.contains("/") || new_name.contains("\\") || new_name == "." || new_name == ".." {
+        return Err("Invalid new name".into());
+    }
+    let (parent_inode, name) = session.resolve_parent_mut(root_inode, &commands[0])?;
+    let inode = parent_inode.get_inode_by_name(&name).ok_or("File or directory not found")?;
+    if parent_inode.get_inode_by_name(new_name).is_some() {
+        return Err("A file or directory with this name already exists".into());
+    }
+    // Renaming changes the directory, so it needs the same permissions as taking the entry out of it
+    session.check_unlink(parent_inode, &inode, &commands[0])?;
+    parent_inode.check_protected(&utils::parent_path(&commands[0]))?;
+    parent_inode.get_inode_by_name_mut(&name).unwrap().set_name(new_name.clone());
+    Ok(())
+}
+
+fn handle_ls(commands: Vec<String>, root_inode: &mut Inode, session: &Session, output: &mut dyn Write) -> Result<(), &'static str> {
+    let long = commands.first().map(String::as_str) == Some("-l");
+    let paths = if long { &commands[1..] } else { &commands[..] };
+    if paths.len() > 1 {
+        return Err("Invalid number of arguments, type 'help ls' to see the usage of the command");
+    }
+    let directory = match paths.first() {
+        Some(path) => session.resolve(root_inode, &utils::normalize_path(path))?,
+        None => root_inode,
+    };
+    if !directory.is_directory() {
+        return Err("Not a directory");
+    }
+    session.check_access(directory, READ)?;
+    for inode in directory.get_children() {
+        let name = if inode.is_directory() {
+            format!("{}/", inode.get_name())
+        } else {
+            inode.get_name().clone()
+        };
+        if long {
+            writeln!(
+                output,
+                "{} {:<8} {:<8} {:>8} {}",
+                inode.permissions_string() + if inode.get_acl().is_empty() && inode.get_default_acl().is_empty() { " " } else { "+" },
+                users::user_name(root_inode, inode.get_uid()),
+                users::group_name(root_inode, inode.get_gid()),
+                inode.get_size(),
+                name
+            ).unwrap();
+        } else {
+            writeln!(output, "{}", name).unwrap();
+        }
+    }
+    Ok(())
+}
+
+fn human_size(bytes: u64) -> String {
+    // Size with the largest unit keeping at least one of it, like 512, 1.5K or 12M
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if size < 10.0 {
+        format!("{:.1}{}", size, UNITS[unit])
+    } else {
+        format!("{:.0}{}", size, UNITS[unit])
+    }
+}
+
+fn write_disk_usage(inode: &Inode, path: &str, summarize: bool, human: bool, session: &Session, output: &mut dyn Write) {
+    // Like du, the subdirectories come before the directory containing them
+    if !summarize && inode.is_directory() {
+        if session.can_access(inode, READ | EXECUTE) {
+            for child in inode.get_children().iter().filter(|child| child.is_directory()) {
+                let child_path = format!("{}/{}", path.trim_end_matches('/'), child.get_name());
+                write_disk_usage(child, &child_path, summarize, human, session, output);
+            }
+        } else {
+            eprintln!("Cannot read directory '{}': Permission denied", path);
+        }
+    }
+    if human {
+        writeln!(output, "{}\t{}\t{}", human_size(inode.get_blocks() * BLOCK_SIZE), human_size(inode.get_size()), path).unwrap();
+    } else {
+        writeln!(output, "{}\t{}\t{}", inode.get_blocks(), inode.get_size(), path).unwrap();
+    }
+}
+
+fn handle_du(commands: Vec<String>, root_inode: &mut Inode, session: &Session, output: &mut dyn Write) -> Result<(), &'static str> {
+    let mut summarize = false;
+    let mut human = false;
+    let mut path: Option<&String> = None;
+    for argument in &commands {
+        match argument.as_str() {
+            "-s" => summarize = true,
+            "-h" => human = true,
+            "-sh" | "-hs" => {
+                summarize = true;
+                human = true;
+            },
+            _ if path.is_none() && !argument.starts_with('-') => path = Some(argument),
+            _ => return Err("Invalid arguments, type 'help du' to see the usage of the command"),
+        }
+    }
+    let path = path.map_or(".", String::as_str);
+    let inode = session.resolve(root_inode, &utils::normalize_path(path))?;
+    write_disk_usage(inode, path, summarize, human, session, output);
+    Ok(())
+}
+
+fn read_file_or_input(path: Option<&String>, root_inode: &Inode, session: &mut Session, input: &mut dyn Read) -> Result<String, String> {
+    // Content of the file at the given path, or everything available in the input
+    match path {
+        Some(path) => session.read_file(root_inode, path),
+        None => {
+            let mut data = String::new();
+            match input.read_to_string(&mut data) {
+                Ok(_) => Ok(data),
+                Err(_) => Err("Error on reading the input".into()),
+            }
+        }
+    }
+}
+
+fn handle_cat(commands: Vec<String>, parent_inode: &mut Inode, session: &mut Session, input: &mut dyn Read, output: &mut dyn Write) -> Result<(), String> {
+    if commands.len() > 1 {
+        return Err("Invalid number of arguments, type 'help cat' to see the usage of the command".into());
+    }
+    let data = read_file_or_input(commands.first(), parent_inode, session, input)?;
+    output.write_all(data.as_bytes()).unwrap();
+    if !data.is_empty() && !data.ends_with('\n') {
+        writeln!(output).unwrap();
+    }
+    Ok(())
+}
+
+fn handle_grep(commands: Vec<String>, parent_inode: &mut Inode, session: &mut Session, input: &mut dyn Read, output: &mut dyn Write) -> Result<(), String> {
+    if commands.is_empty() || commands.len() > 2 {
+        return Err("Invalid number of arguments, type 'help grep' to see the usage of the command".into());
+    }
+    let data = read_file_or_input(commands.get(1), parent_inode, session, input)?;
+    for line in data.lines().filter(|line| line.contains(commands[0].as_str())) {
+        writeln!(output, "{}", line).unwrap();
+    }
+    Ok(())
+}
+
+fn handle_wc(commands: Vec<String>, parent_inode: &mut Inode, session: &mut Session, input: &mut dyn Read, output: &mut dyn Write) -> Result<(), String> {
+    let (option, name) = match commands.first().map(String::as_str) {
+        Some("-l") | Some("-w") | Some("-c") => (Some(commands[0].clone()), commands.get(1)),
+        _ => (None, commands.first()),
+    };
+    if commands.len() > 2 || (option.is_none() && commands.len() > 1) {
+        return Err("Invalid number of arguments, type 'help wc' to see the usage of the command".into());
+    }
+    let data = read_file_or_input(name, parent_inode, session, input)?;
+    let lines = data.lines().count();
+    let words = data.split_whitespace().count();
+    let bytes = data.len();
+    match option.as_deref() {
+        Some("-l") => writeln!(output, "{}", lines).unwrap(),
+        Some("-w") => writeln!(output, "{}", words).unwrap(),
+        Some("-c") => writeln!(output, "{}", bytes).unwrap(),
+        _ => writeln!(output, "{} {} {}", lines, words, bytes).unwrap(),
+    }
+    Ok(())
+}
+
+fn handle_echo(commands: Vec<String>, output: &mut dyn Write) -> Result<(), &'static str> {
+    if commands.first().map(String::as_str) == Some("-n") {
+        write!(output, "{}", commands[1..].join(" ")).unwrap();
+    } else {
+        writeln!(output, "{}", commands.join(" ")).unwrap();
+    }
+    Ok(())
+}
+
+fn handle_move(commands: Vec<String>, root_inode: &mut Inode, session: &mut Session) -> Result<(), String> {
+    if commands.len() != 2 {
+        return Err("Invalid number of arguments, type 'help move' to see the usage of the command".into());
+    }
+    let (source_parent_path, name) = utils::split_path(&commands[0])?;
+    let destination_path = utils::normalize_path(&commands[1]);
+    if destination_path.starts_with(&utils::normalize_path(&commands[0])) {
+        return Err("Cannot move a directory into itself".into());
+    }
+
+    // Leaving the source directory needs the same permissions as removing the entry, and entering the
+    // destination the same as creating it. Everything is checked before changing anything
+    let source_parent = session.resolve(root_inode, &source_parent_path)?;
+    let inode = source_parent.get_inode_by_name(&name).ok_or("File or directory not found")?;
+    session.check_access(source_parent, WRITE | EXECUTE)?;
+    if source_parent.get_permissions() & STICKY != 0 && inode.get_uid() != session.uid && source_parent.get_uid() != session.uid && session.uid != ROOT_UID {
+        return Err("Permission denied".into());
+    }
+    source_parent.check_protected(&utils::parent_path(&commands[0]))?;
+    inode.check_protected(&commands[0])?;
+    let destination = session.resolve(root_inode, &destination_path)?;
+    if !destination.is_directory() {
+        return Err(format!("'{}' is not a directory", commands[1]));
+    }
+    if destination.get_inode_by_name(&name).is_some() {
+        return Err("A file or directory with this name already exists".into());
+    }
+    session.check_create(destination, &commands[1])?;
+    let mut added = if inode.is_directory() { quota::tree_usage(&inode) } else { Usage::default() };
+    added.bytes += inode.content_size();
+    added.inodes += 1;
+    let warnings = quota::check_move(root_inode, &source_parent_path, &destination_path, added)?;
+    session.warnings.extend(warnings);
+
+    // The inode keeps its owner, group and ACL, it isn't created again in the destination
+    let source_parent = root_inode.get_inode_by_path_mut(&source_parent_path).unwrap();
+    source_parent.remove_inode(inode.clone());
+    root_inode.refresh_sizes(&source_parent_path);
+    root_inode.get_inode_by_path_mut(&destination_path).unwrap().insert_inode(inode);
+    root_inode.refresh_sizes(&destination_path);
+    Ok(())
+}
+
+fn handle_touch(commands: Vec<String>, root_inode: &mut Inode, session: &mut Session) -> Result<(), String> {
+    if commands.len() != 1 {
+        return Err("Invalid number of arguments, type 'help touch' to see the usage of the command".into());
+    }
+    let (parent_path, _) = utils::split_path(&commands[0])?;
+    let quota = quota::check(root_inode, session.uid, &parent_path, Usage { bytes: 0, inodes: 1 });
+    let (directory, name) = session.resolve_parent_mut(root_inode, &commands[0])?;
+    if directory.get_inode_by_name(&name).is_none() {
+        session.check_create(directory, &utils::parent_path(&commands[0]))?;
+        session.warnings.extend(quota?);
+        directory.write_file(&name, "", false, session.uid, session.gid)?;
+        root_inode.refresh_sizes(&parent_path);
+        return Ok(());
+    }
+    let inode = directory.get_inode_by_name_mut(&name).unwrap();
+    if inode.get_uid() != session.uid {
+        session.check_access(inode, WRITE)?;
+    }
+    inode.check_protected(&commands[0])?;
+    inode.touch();
+    Ok(())
+}
+
+fn handle_write(commands: Vec<String>, root_inode: &mut Inode, session: &mut Session) -> Result<(), String> {
+    if commands.len() < 2 {
+        return Err("Invalid number of arguments, type 'help write' to see the usage of the command".into());
+    }
+    session.write_file(root_inode, &commands[0], &commands[1..].join(" "), false)
+}
+
+struct SessionFiles<'a> {
+    // Files of the VFS as seen by the editor, with the permissions of the session
+    session: &'a mut Session,
+    root: &'a mut Inode,
+}
+
+impl SessionFiles<'_> {
+    fn check_passphrase(&self, path: &str) -> Result<(), String> {
+        // The passphrase can't be asked while the editor has the terminal, it has to be given before opening it
+        let encrypted = self.root.get_inode_by_path(&utils::normalize_path(path)).is_some_and(Inode::is_encrypted);
+        if encrypted && self.session.passphrase.is_none() {
+            return Err(format!("Passphrase required for '{}', open it with edit first", path));
+        }
+        Ok(())
+    }
+}
+
+impl Files for SessionFiles<'_> {
+    fn read(&mut self, path: &str) -> Result<Option<String>, String> {
+        if self.root.get_inode_by_path(&utils::normalize_path(path)).is_none() {
+            return Ok(None);
+        }
+        self.check_passphrase(path)?;
+        self.session.read_file(self.root, path).map(Some)
+    }
+
+    fn write(&mut self, path: &str, data: &str) -> Result<(), String> {
+        // Each file saved by the editor is audited like a write, without its content
+        let user = self.session.user.clone();
+        let passphrase = self.check_passphrase(path);
+        let session = &mut *self.session;
+        audit::audited(self.root, &user, &[String::from("write"), path.to_string()], |root| {
+            passphrase?;
+            session.write_file(root, path, data, false)
+        })
+    }
+}
+
+fn handle_edit(commands: Vec<String>, root_inode: &mut Inode, session: &mut Session) -> Result<(), String> {
+    if commands.len() != 1 {
+        return Err("Invalid number of arguments, type 'help edit' to see the usage of the command".into());
+    }
+    let inode = session.resolve(root_inode, &utils::normalize_path(&commands[0]))?;
+    session.check_access(inode, READ | WRITE)?;
+    inode.check_protected(&commands[0])?;
+    let data = session.read_file(root_inode, &commands[0])?;
+    let mut registers = std::mem::take(&mut session.registers);
+    let result = edit_file(&commands[0], &data, &mut SessionFiles { session, root: root_inode }, &mut registers);
+    session.registers = registers;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_command, SessionFiles};
+    use crate::audit;
+    use crate::commands::Files;
+    use crate::session::{Session, ROOT_GID, ROOT_UID};
+    use crate::types::{Inode, DIR_MODE};
+    use crate::users;
+    use crate::utils;
+
+    fn run(root: &mut Inode, session: &mut Session, line: &str) -> Result<String, String> {
+        let commands = line.split(' ').map(String::from).collect();
+        let mut output: Vec<u8> = Vec::new();
+        run_command(commands, root, session, &mut std::io::empty(), &mut output)?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    fn setup() -> (Inode, Session) {
+        let mut root = Inode::new(DIR_MODE, String::from("/"), ROOT_UID, ROOT_GID);
+        users::bootstrap(&mut root);
+        (root, Session::root())
+    }
+
+    fn user_session(root: &Inode, name: &str) -> Session {
+        let user = users::find_user(root, name).unwrap();
+        Session::for_user(&user, users::user_groups(root, &user))
+    }
+
+    fn blocks(root: &Inode, path: &str) -> u64 {
+        root.get_inode_by_path(&utils::normalize_path(path)).unwrap().get_blocks()
+    }
+
+    #[test]
+    fn move_updates_the_sizes_of_both_directories() {
+        let (mut root, mut session) = setup();
+        for line in ["new directory /tmp/a", "new directory /tmp/a/b", "new directory /tmp/c", "write /tmp/a/b/f xxx"] {
+            run(&mut root, &mut session, line).unwrap();
+        }
+        let total = blocks(&root, "/");
+        assert_eq!((blocks(&root, "/tmp/a"), blocks(&root, "/tmp/c")), (3, 1));
+        run(&mut root, &mut session, "move /tmp/a/b/f /tmp/c").unwrap();
+        assert_eq!((blocks(&root, "/tmp/a"), blocks(&root, "/tmp/c")), (2, 2));
+        assert_eq!(blocks(&root, "/"), total);
+        assert!(root.get_inode_by_path(&utils::normalize_path("/tmp/c/f")).is_some());
+        assert!(run(&mut root, &mut session, "move /tmp/a /tmp/a/b").is_err());
+    }
+
+    #[test]
+    fn rename_follows_the_sticky_bit() {
+        let (mut root, mut session) = setup();
+        let mut user = user_session(&root, users::DEFAULT_USER);
+        let other_user = users::User {
+            name: String::from("other"),
+            uid: 2000,
+            gid: 2000,
+            home: String::new(),
+        };
+        let mut other = Session::for_user(&other_user, vec![2000]);
+        for line in ["new directory /tmp/s", "chmod 1777 /tmp/s", "write /tmp/s/u x"] {
+            run(&mut root, &mut user, line).unwrap();
+        }
+        run(&mut root, &mut other, "write /tmp/s/o x").unwrap();
+        assert!(run(&mut root, &mut other, "rename /tmp/s/u v").is_err());
+        run(&mut root, &mut user, "rename /tmp/s/o p").unwrap();
+
+        // Root renames anything, even in a sticky directory of someone else
+        run(&mut root, &mut other, "write /tmp/s/o x").unwrap();
+        run(&mut root, &mut session, "rename /tmp/s/o q").unwrap();
+        assert_eq!(root.get_inode_by_path(&utils::normalize_path("/tmp/s")).unwrap().get_children_names(), ["u", "p", "q"]);
+    }
+
+    #[test]
+    fn userdel_keeps_the_user_when_the_home_directory_cannot_be_removed() {
+        let (mut root, mut session) = setup();
+        let mut user = user_session(&root, users::DEFAULT_USER);
+        run(&mut root, &mut user, "write /home/user/f x").unwrap();
+        run(&mut root, &mut session, "immutable on /home/user/f").unwrap();
+        assert!(run(&mut root, &mut session, "userdel -r user").is_err());
+        assert!(users::find_user(&root, users::DEFAULT_USER).is_some());
+
+        run(&mut root, &mut session, "immutable off /home/user/f").unwrap();
+        run(&mut root, &mut session, "userdel -r user").unwrap();
+        assert!(users::find_user(&root, users::DEFAULT_USER).is_none());
+        assert!(root.get_inode_by_path(&utils::normalize_path("/home/user")).is_none());
+    }
+
+    #[test]
+    fn ls_lists_entries_with_their_permissions() {
+        let (mut root, mut session) = setup();
+        for line in ["new directory /tmp/l", "new directory /tmp/l/sub", "write /tmp/l/f x", "chmod 4750 /tmp/l/f", "setfacl -m u:user:r-- /tmp/l/sub"] {
+            run(&mut root, &mut session, line).unwrap();
+        }
+        assert_eq!(run(&mut root, &mut session, "ls /tmp/l").unwrap(), "sub/\nf\n");
+        let lines: Vec<Vec<String>> = run(&mut root, &mut session, "ls -l /tmp/l")
+            .unwrap()
+            .lines()
+            .map(|line| line.split_whitespace().map(String::from).collect())
+            .collect();
+        assert_eq!(lines[0][..3], ["drwxr-xr-x+", "root", "root"]);
+        assert_eq!(lines[1][..3], ["-rwsr-x---", "root", "root"]);
+        assert!(run(&mut root, &mut session, "ls /tmp/l/f").is_err());
+    }
+
+    #[test]
+    fn chmod_sets_the_mask_of_the_acl() {
+        let (mut root, mut session) = setup();
+        for line in ["write /tmp/m x", "chmod 664 /tmp/m", "setfacl -m u:user:rw- /tmp/m", "chmod g=r /tmp/m"] {
+            run(&mut root, &mut session, line).unwrap();
+        }
+        let file = root.get_inode_by_path(&utils::normalize_path("/tmp/m")).unwrap();
+        assert_eq!((file.get_permissions(), file.acl_mask()), (0o644, 0o4));
+        assert!(run(&mut root, &mut session, "getfacl /tmp/m").unwrap().contains("mask::r--"));
+    }
+
+    #[test]
+    fn encrypting_is_limited_by_quotas() {
+        let (mut root, mut session) = setup();
+        session.passphrase = Some(String::from("secret"));
+        for line in ["new directory /tmp/q", "write /tmp/q/f xxxxxxxxxx", "setquota -d /tmp/q 0 20 0 0"] {
+            run(&mut root, &mut session, line).unwrap();
+        }
+        assert!(run(&mut root, &mut session, "encrypt /tmp/q/f").is_err());
+        assert!(!root.get_inode_by_path(&utils::normalize_path("/tmp/q/f")).unwrap().is_encrypted());
+
+        run(&mut root, &mut session, "setquota -d /tmp/q 0 0 0 0").unwrap();
+        run(&mut root, &mut session, "encrypt /tmp/q/f").unwrap();
+        assert!(root.get_inode_by_path(&utils::normalize_path("/tmp/q/f")).unwrap().is_encrypted());
+    }
+
+    #[test]
+    fn files_saved_by_the_editor_are_audited() {
+        let (mut root, _) = setup();
+        let mut user = user_session(&root, users::DEFAULT_USER);
+        let mut files = SessionFiles { session: &mut user, root: &mut root };
+        files.write("/tmp/e", "secret").unwrap();
+        assert!(files.write("/etc/e", "secret").is_err());
+
+        let data = root.get_inode_by_path(&utils::normalize_path(audit::AUDIT_LOG_PATH)).unwrap().read_content(None).unwrap();
+        let entries: Vec<(String, Option<String>, String)> =
+            audit::read_entries(&data).into_iter().map(|entry| (entry.command, entry.path, entry.result)).collect();
+        assert_eq!(entries[0], (String::from("write /tmp/e"), Some(String::from("/tmp/e")), String::from("ok")));
+        assert_eq!(entries[1].1.as_deref(), Some("/etc/e"));
+        assert!(entries[1].2.starts_with("error: "));
+        assert!(!data.contains("secret"));
+    }
+
+    #[test]
+    fn du_summarizes_in_human_units() {
+        let (mut root, mut session) = setup();
+        for line in ["new directory /tmp/d", "new directory /tmp/d/sub", &format!("write /tmp/d/sub/f {}", "x".repeat(3000))] {
+            run(&mut root, &mut session, line).unwrap();
+        }
+        let output = run(&mut root, &mut session, "du -s -h /tmp/d").unwrap();
+        let fields: Vec<&str> = output.trim_end().split('\t').collect();
+        assert_eq!(output.lines().count(), 1);
+        assert_eq!((fields[0], fields[2]), ("5.0K", "/tmp/d"));
+        assert_eq!(run(&mut root, &mut session, "du /tmp/d").unwrap().lines().count(), 2);
     }
 }
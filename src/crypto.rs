@@ -0,0 +1,87 @@
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use pbkdf2::pbkdf2_hmac;
+use sha2::Sha256;
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const KEY_ROUNDS: u32 = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct EncryptedData {
+    salt: [u8; SALT_LENGTH],
+    nonce: [u8; NONCE_LENGTH],
+    ciphertext: Vec<u8>, // includes the authentication tag
+}
+
+impl EncryptedData {
+    pub fn size(&self) -> usize {
+        SALT_LENGTH + NONCE_LENGTH + self.ciphertext.len()
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Key {
+    let mut key = [0u8; 32];
+    pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, KEY_ROUNDS, &mut key);
+    Key::from(key)
+}
+
+pub fn encrypt(passphrase: &str, plaintext: &str, associated_data: &[u8]) -> EncryptedData {
+    // Every encryption uses a new salt and nonce, so the same content never gives the same ciphertext
+    let mut salt = [0u8; SALT_LENGTH];
+    let mut nonce = [0u8; NONCE_LENGTH];
+    getrandom::getrandom(&mut salt).expect("Error on generating a random salt");
+    getrandom::getrandom(&mut nonce).expect("Error on generating a random nonce");
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt));
+    let payload = Payload {
+        msg: plaintext.as_bytes(),
+        aad: associated_data,
+    };
+    let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), payload).expect("Error on encrypting data");
+    EncryptedData { salt, nonce, ciphertext }
+}
+
+pub fn decrypt(passphrase: &str, data: &EncryptedData, associated_data: &[u8]) -> Result<String, &'static str> {
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &data.salt));
+    let payload = Payload {
+        msg: &data.ciphertext,
+        aad: associated_data,
+    };
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&data.nonce), payload)
+        .map_err(|_| "Wrong passphrase or corrupted data")?;
+    String::from_utf8(plaintext).map_err(|_| "Decrypted data is not valid text")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decrypt, encrypt};
+
+    #[test]
+    fn content_round_trips() {
+        let data = encrypt("passphrase", "secret content\n", b"1");
+        assert_eq!(data.size(), 16 + 12 + "secret content\n".len() + 16);
+        assert_eq!(decrypt("passphrase", &data, b"1"), Ok(String::from("secret content\n")));
+        assert_ne!(encrypt("passphrase", "secret content\n", b"1").ciphertext, data.ciphertext);
+    }
+
+    #[test]
+    fn wrong_passphrase_or_tampered_data_fails() {
+        let data = encrypt("passphrase", "secret", b"1");
+        assert!(decrypt("other", &data, b"1").is_err());
+        let mut tampered = data.clone();
+        tampered.ciphertext[0] ^= 1;
+        assert!(decrypt("passphrase", &tampered, b"1").is_err());
+        let mut tampered = data.clone();
+        tampered.nonce[0] ^= 1;
+        assert!(decrypt("passphrase", &tampered, b"1").is_err());
+    }
+
+    #[test]
+    fn data_is_bound_to_its_associated_data() {
+        // Content copied to another inode, with another serial number, doesn't decrypt
+        let data = encrypt("passphrase", "secret", &7u64.to_le_bytes());
+        assert!(decrypt("passphrase", &data, &8u64.to_le_bytes()).is_err());
+        assert!(decrypt("passphrase", &data, &7u64.to_le_bytes()).is_ok());
+    }
+}
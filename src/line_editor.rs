@@ -0,0 +1,398 @@
+use std::fs;
+use std::io::{self, stdout, IsTerminal, Stdout, Write};
+use std::path::PathBuf;
+use crossterm::cursor::MoveToColumn;
+use crossterm::event::{read, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::QueueableCommand;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType};
+
+const HISTORY_FILE: &str = ".vfs_history";
+const HISTORY_LIMIT: usize = 500;
+
+pub struct LineEditor {
+    history: Vec<String>,
+    history_path: Option<PathBuf>,
+    interactive: bool,
+}
+
+struct Line {
+    data: Vec<char>,
+    cursor: usize,
+}
+
+impl Line {
+    fn new() -> Line {
+        Line {
+            data: Vec::new(),
+            cursor: 0,
+        }
+    }
+
+    fn from_str(value: &str) -> Line {
+        let data: Vec<char> = value.chars().collect();
+        let cursor = data.len();
+        Line { data, cursor }
+    }
+
+    fn content(&self) -> String {
+        self.data.iter().collect()
+    }
+
+    fn insert(&mut self, c: char) {
+        self.data.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    fn insert_str(&mut self, value: &str) {
+        for c in value.chars() {
+            self.insert(c);
+        }
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.data.remove(self.cursor);
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.cursor < self.data.len() {
+            self.data.remove(self.cursor);
+        }
+    }
+
+    fn move_left(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+        }
+    }
+
+    fn move_right(&mut self) {
+        if self.cursor < self.data.len() {
+            self.cursor += 1;
+        }
+    }
+
+    fn word_start(&self) -> usize {
+        // Index of the first character of the word under the cursor, which a pipe ends like a space
+        let mut start = self.cursor;
+        while start > 0 && !self.data[start - 1].is_whitespace() && self.data[start - 1] != '|' {
+            start -= 1;
+        }
+        start
+    }
+}
+
+impl LineEditor {
+    pub fn new() -> LineEditor {
+        let interactive = io::stdin().is_terminal();
+        let history_path = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+        let mut history: Vec<String> = Vec::new();
+        if interactive {
+            if let Some(path) = &history_path {
+                if let Ok(content) = fs::read_to_string(path) {
+                    history = content.lines().filter(|line| !line.is_empty()).map(String::from).collect();
+                }
+            }
+        }
+        if history.len() > HISTORY_LIMIT {
+            history.drain(..history.len() - HISTORY_LIMIT);
+        }
+        LineEditor {
+            history,
+            history_path,
+            interactive,
+        }
+    }
+
+    pub fn read_line(&mut self, prompt: &str, commands: &[&str], entries: &[String]) -> Option<String> {
+        // Read a line from the user, returning None when the input is closed (Ctrl+D or end of file)
+        if !self.interactive {
+            let mut command = String::new();
+            return match io::stdin().read_line(&mut command) {
+                Ok(0) | Err(_) => None,
+                Ok(_) => Some(command.trim_end_matches(['\n', '\r']).to_string()),
+            };
+        }
+
+        let mut terminal: Stdout = stdout();
+        enable_raw_mode().expect("Raw Mode of terminal not enabled");
+        let mut line = Line::new();
+        let mut history_index = self.history.len();
+        let mut draft = String::new();
+        redraw(&mut terminal, prompt, &line);
+
+        let result = loop {
+            let event = match read() {
+                Ok(Event::Key(event)) => event,
+                Ok(_) => continue,
+                Err(_) => break None,
+            };
+            if event.kind != KeyEventKind::Press {
+                continue;
+            }
+            match handle_key(event) {
+                LineAction::Insert(c) => line.insert(c),
+                LineAction::Backspace => line.backspace(),
+                LineAction::Delete => line.delete(),
+                LineAction::Left => line.move_left(),
+                LineAction::Right => line.move_right(),
+                LineAction::Home => line.cursor = 0,
+                LineAction::End => line.cursor = line.data.len(),
+                LineAction::HistoryPrevious => {
+                    if history_index > 0 {
+                        if history_index == self.history.len() {
+                            draft = line.content();
+                        }
+                        history_index -= 1;
+                        line = Line::from_str(&self.history[history_index]);
+                    }
+                },
+                LineAction::HistoryNext => {
+                    if history_index < self.history.len() {
+                        history_index += 1;
+                        line = if history_index == self.history.len() {
+                            Line::from_str(&draft)
+                        } else {
+                            Line::from_str(&self.history[history_index])
+                        };
+                    }
+                },
+                LineAction::Complete => complete(&mut terminal, prompt, &mut line, commands, entries),
+                LineAction::Submit => break Some(line.content()),
+                LineAction::Cancel => {
+                    terminal.write_all(b"^C").unwrap();
+                    break Some(String::new());
+                },
+                LineAction::Close => {
+                    if line.data.is_empty() {
+                        break None;
+                    }
+                    line.delete();
+                },
+                LineAction::Ignore => continue,
+            }
+            redraw(&mut terminal, prompt, &line);
+        };
+
+        terminal.write_all(b"\r\n").unwrap();
+        terminal.flush().unwrap();
+        disable_raw_mode().expect("Exit raw mode of terminal failed");
+
+        if let Some(command) = &result {
+            self.add_history(command);
+        }
+        result
+    }
+
+    fn add_history(&mut self, command: &str) {
+        if command.trim().is_empty() || self.history.last().map(String::as_str) == Some(command) {
+            return;
+        }
+        self.history.push(command.to_string());
+        if self.history.len() > HISTORY_LIMIT {
+            self.history.remove(0);
+        }
+        if let Some(path) = &self.history_path {
+            // History is kept on the host, since the virtual filesystem does not outlive the program. The whole file
+            // is rewritten after every command with the kept ones, so that it never holds more than HISTORY_LIMIT of them
+            let data: String = self.history.iter().map(|command| format!("{}\n", command)).collect();
+            let _ = fs::write(path, data);
+        }
+    }
+}
+
+enum LineAction {
+    Insert(char),
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+    HistoryPrevious,
+    HistoryNext,
+    Complete,
+    Submit,
+    Cancel,
+    Close,
+    Ignore,
+}
+
+fn handle_key(event: KeyEvent) -> LineAction {
+    if event.modifiers.contains(KeyModifiers::CONTROL) {
+        return match event.code {
+            KeyCode::Char('c') => LineAction::Cancel,
+            KeyCode::Char('d') => LineAction::Close,
+            KeyCode::Char('a') => LineAction::Home,
+            KeyCode::Char('e') => LineAction::End,
+            KeyCode::Char('b') => LineAction::Left,
+            KeyCode::Char('f') => LineAction::Right,
+            KeyCode::Char('p') => LineAction::HistoryPrevious,
+            KeyCode::Char('n') => LineAction::HistoryNext,
+            _ => LineAction::Ignore,
+        };
+    }
+    match event.code {
+        KeyCode::Char(x) => LineAction::Insert(x),
+        KeyCode::Backspace => LineAction::Backspace,
+        KeyCode::Delete => LineAction::Delete,
+        KeyCode::Left => LineAction::Left,
+        KeyCode::Right => LineAction::Right,
+        KeyCode::Home => LineAction::Home,
+        KeyCode::End => LineAction::End,
+        KeyCode::Up => LineAction::HistoryPrevious,
+        KeyCode::Down => LineAction::HistoryNext,
+        KeyCode::Tab => LineAction::Complete,
+        KeyCode::Enter => LineAction::Submit,
+        _ => LineAction::Ignore,
+    }
+}
+
+fn redraw(terminal: &mut Stdout, prompt: &str, line: &Line) {
+    // Rewrite the whole prompt line and put the cursor back where it belongs
+    terminal.queue(MoveToColumn(0)).unwrap();
+    terminal.queue(Clear(ClearType::CurrentLine)).unwrap();
+    terminal.write_all(prompt.as_bytes()).unwrap();
+    terminal.write_all(line.content().as_bytes()).unwrap();
+    let column = prompt.chars().count() + line.cursor;
+    terminal.queue(MoveToColumn(column as u16)).unwrap();
+    terminal.flush().unwrap();
+}
+
+fn completions(line: &Line, commands: &[&str], entries: &[String]) -> (String, Vec<String>) {
+    // Word under the cursor, with the sorted command names (first word of a pipeline stage) or directory entries
+    // it can be completed with
+    let start = line.word_start();
+    let prefix: String = line.data[start..line.cursor].iter().collect();
+    let before: String = line.data[..start].iter().collect();
+    let before = before.trim_end();
+    let is_command = before.is_empty() || before.ends_with('|');
+    let mut candidates: Vec<String> = if is_command {
+        commands.iter().map(|command| command.to_string()).collect()
+    } else {
+        entries.to_vec()
+    };
+    candidates.retain(|candidate| candidate.starts_with(&prefix));
+    candidates.sort();
+    candidates.dedup();
+    (prefix, candidates)
+}
+
+fn complete(terminal: &mut Stdout, prompt: &str, line: &mut Line, commands: &[&str], entries: &[String]) {
+    // Complete the word under the cursor, or list the candidates when they have nothing more in common
+    let (prefix, candidates) = completions(line, commands, entries);
+    match candidates.len() {
+        0 => {},
+        1 => {
+            line.insert_str(&candidates[0][prefix.len()..]);
+            line.insert(' ');
+        },
+        _ => {
+            let common = common_prefix(&candidates);
+            if common.len() > prefix.len() {
+                line.insert_str(&common[prefix.len()..]);
+            } else {
+                terminal.write_all(b"\r\n").unwrap();
+                terminal.write_all(candidates.join("  ").as_bytes()).unwrap();
+                terminal.write_all(b"\r\n").unwrap();
+                redraw(terminal, prompt, line);
+            }
+        },
+    }
+}
+
+fn common_prefix(candidates: &[String]) -> String {
+    let mut common = candidates[0].clone();
+    for candidate in &candidates[1..] {
+        while !candidate.starts_with(&common) {
+            common.pop();
+        }
+    }
+    common
+}
+
+pub fn read_password(prompt: &str) -> Option<String> {
+    // Read a line from the user without echoing it, returning None when the input is closed
+    let mut terminal: Stdout = stdout();
+    if !io::stdin().is_terminal() {
+        let mut password = String::new();
+        return match io::stdin().read_line(&mut password) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(password.trim_end_matches(['\n', '\r']).to_string()),
+        };
+    }
+
+    terminal.write_all(prompt.as_bytes()).unwrap();
+    terminal.flush().unwrap();
+    enable_raw_mode().expect("Raw Mode of terminal not enabled");
+    let mut password = String::new();
+    let result = loop {
+        let event = match read() {
+            Ok(Event::Key(event)) if event.kind == KeyEventKind::Press => event,
+            Ok(_) => continue,
+            Err(_) => break None,
+        };
+        match handle_key(event) {
+            LineAction::Insert(c) => password.push(c),
+            LineAction::Backspace => {
+                password.pop();
+            },
+            LineAction::Submit => break Some(password),
+            LineAction::Cancel | LineAction::Close => break None,
+            _ => {},
+        }
+    };
+    terminal.write_all(b"\r\n").unwrap();
+    terminal.flush().unwrap();
+    disable_raw_mode().expect("Exit raw mode of terminal failed");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{common_prefix, completions, Line};
+
+    #[test]
+    fn line_edits_at_the_cursor() {
+        let mut line = Line::from_str("ct");
+        line.move_left();
+        line.insert('a');
+        assert_eq!((line.content(), line.cursor), (String::from("cat"), 2));
+        line.move_right();
+        line.move_right();
+        line.insert_str(" f");
+        line.backspace();
+        assert_eq!((line.content(), line.cursor), (String::from("cat "), 4));
+        line.cursor = 0;
+        line.delete();
+        line.backspace();
+        line.move_left();
+        assert_eq!((line.content(), line.cursor), (String::from("at "), 0));
+        assert_eq!(Line::new().content(), "");
+    }
+
+    #[test]
+    fn first_words_complete_to_commands() {
+        let commands = ["cat", "chmod", "grep"];
+        let entries = vec![String::from("notes"), String::from("chapter"), String::from("cats")];
+        assert_eq!(completions(&Line::from_str("c"), &commands, &entries), (String::from("c"), vec![String::from("cat"), String::from("chmod")]));
+        assert_eq!(completions(&Line::from_str("cat c"), &commands, &entries).1, ["cats", "chapter"]);
+        assert_eq!(completions(&Line::from_str("cat notes |g"), &commands, &entries).1, ["grep"]);
+        assert_eq!(completions(&Line::from_str("cat notes | grep n"), &commands, &entries).1, ["notes"]);
+
+        // The word ends at the cursor
+        let mut line = Line::from_str("cat x");
+        line.move_left();
+        assert_eq!(completions(&line, &commands, &entries), (String::new(), vec![String::from("cats"), String::from("chapter"), String::from("notes")]));
+    }
+
+    #[test]
+    fn common_prefix_of_the_candidates() {
+        let candidates = [String::from("chapter"), String::from("chmod"), String::from("ché")];
+        assert_eq!(common_prefix(&candidates), "ch");
+        assert_eq!(common_prefix(&candidates[..1]), "chapter");
+        assert_eq!(common_prefix(&[String::from("été"), String::from("étà")]), "ét");
+    }
+}
@@ -1,15 +1,23 @@
-use std::io::{self, stdout, Write};
+use std::io::{stdout, Write};
 use crossterm::cursor::MoveTo;
 use crossterm::QueueableCommand;
 use crossterm::terminal::{Clear, ClearType};
 
+mod audit;
 mod commands_handler;
+mod crypto;
+mod quota;
 mod types;
 mod utils;
 mod commands;
+mod line_editor;
+mod shell;
+mod session;
+mod users;
 
 fn create_root() -> types::Inode {
-    let root = types::Inode::new(types::DIR_MODE, String::from("/"));
+    let mut root = types::Inode::new(types::DIR_MODE, String::from("/"), session::ROOT_UID, session::ROOT_GID);
+    users::bootstrap(&mut root);
     root
 }
 
@@ -17,24 +25,26 @@ fn main() {
     let mut terminal = stdout();
     terminal.queue(Clear(ClearType::All)).unwrap();
     terminal.queue(MoveTo(0,0)).unwrap();
-    terminal.write(b"Welcome to VFS\n").unwrap();
+    terminal.write_all(b"Welcome to VFS\n").unwrap();
+    terminal.flush().unwrap();
     let root = create_root();
+    // The shell starts with the unprivileged default user, administration goes through sudo or login
+    let user = users::find_user(&root, users::DEFAULT_USER).expect("Default user not found");
+    let groups = users::user_groups(&root, &user);
+    let mut session = session::Session::for_user(&user, groups);
     let mut actual_inode = root;
+    let mut line_editor = line_editor::LineEditor::new();
     loop {
         // actual_inode.print_inode_path(&mut terminal);
 
-        let mut command = String::new();
-        io::stdin()
-            .read_line(&mut command)
-            .expect("Error on reading line");
+        let entries = actual_inode.get_children_names();
+        let prompt = format!("{}@vfs> ", session.user);
+        let command = match line_editor.read_line(&prompt, commands_handler::COMMANDS, &entries) {
+            Some(command) => command,
+            None => break,
+        };
 
-        let command_vector: Vec<String> = command
-            .trim()
-            .split_whitespace()
-            .map(String::from)
-            .collect();
-
-        let quit = commands_handler::handle_commands(command_vector, &mut actual_inode);
+        let quit = shell::run_command_line(&command, &mut actual_inode, &mut session);
         if quit {
             break;
         }
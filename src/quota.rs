@@ -0,0 +1,128 @@
+use crate::session::ROOT_UID;
+use crate::types::{Inode, Quota};
+use crate::users;
+
+pub const QUOTA_PATH: &str = "/etc/quota";
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Usage {
+    pub bytes: u64,
+    pub inodes: u64,
+}
+
+pub fn read_user_quotas(root: &Inode) -> Vec<(String, Quota)> {
+    // Each line of /etc/quota is name:soft_bytes:hard_bytes:soft_inodes:hard_inodes
+    users::read_system_file(root, QUOTA_PATH)
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            if fields.len() != 5 {
+                return None;
+            }
+            Some((fields[0].to_string(), Quota {
+                soft_bytes: fields[1].parse().ok()?,
+                hard_bytes: fields[2].parse().ok()?,
+                soft_inodes: fields[3].parse().ok()?,
+                hard_inodes: fields[4].parse().ok()?,
+            }))
+        })
+        .collect()
+}
+
+pub fn user_quota(root: &Inode, name: &str) -> Option<Quota> {
+    read_user_quotas(root).into_iter().find(|(user, _)| user == name).map(|(_, quota)| quota)
+}
+
+pub fn set_user_quota(root: &mut Inode, name: &str, quota: Option<Quota>) {
+    // Replace the quota of the user, or remove it when None
+    let mut quotas: Vec<(String, Quota)> = read_user_quotas(root).into_iter().filter(|(user, _)| user != name).collect();
+    if let Some(quota) = quota {
+        quotas.push((name.to_string(), quota));
+    }
+    let data: String = quotas
+        .iter()
+        .map(|(user, quota)| format!("{}:{}:{}:{}:{}\n", user, quota.soft_bytes, quota.hard_bytes, quota.soft_inodes, quota.hard_inodes))
+        .collect();
+    users::write_system_file(root, QUOTA_PATH, data, 0o644);
+}
+
+pub fn user_usage(root: &Inode, uid: u32) -> Usage {
+    // Content bytes and number of the inodes owned by the user, anywhere in the tree
+    let mut usage = Usage::default();
+    root.walk(&mut |inode| {
+        if inode.get_uid() == uid {
+            usage.bytes += inode.content_size();
+            usage.inodes += 1;
+        }
+    });
+    usage
+}
+
+pub fn tree_usage(directory: &Inode) -> Usage {
+    // Content bytes and number of the inodes inside the directory, not counting the directory itself
+    let mut usage = Usage::default();
+    directory.walk(&mut |inode| {
+        usage.bytes += inode.content_size();
+        usage.inodes += 1;
+    });
+    usage.inodes -= 1;
+    usage
+}
+
+fn exceeds(value: u64, limit: u64) -> bool {
+    limit != 0 && value > limit
+}
+
+fn directory_limits(root: &Inode, path: &[String], from_depth: usize, limits: &mut Vec<(String, Quota, Usage)>) {
+    // Quotas of the directories on the path, skipping the ones above the given depth
+    let mut directory = Some(root);
+    for depth in 0..=path.len() {
+        let Some(inode) = directory else { break };
+        if let Some(quota) = inode.get_quota().filter(|_| depth >= from_depth) {
+            limits.push((format!("'/{}'", path[..depth].join("/")), quota, tree_usage(inode)));
+        }
+        directory = path.get(depth).and_then(|name| inode.get_children().iter().find(|child| child.get_name() == name));
+    }
+}
+
+pub fn check(root: &Inode, uid: u32, parent_path: &[String], added: Usage) -> Result<Vec<String>, String> {
+    // Fail if adding content to the directory at the path, charged to the user, goes over a hard limit of the
+    // user or of the directory and its ancestors, and return the warnings of the soft limits it goes over.
+    // Root has no user quota, and the usage is only walked for the limits that apply
+    let mut limits: Vec<(String, Quota, Usage)> = Vec::new();
+    if added.bytes == 0 && added.inodes == 0 {
+        return Ok(Vec::new());
+    }
+    if uid != ROOT_UID {
+        let name = users::user_name(root, uid);
+        if let Some(quota) = user_quota(root, &name).filter(|quota| *quota != Quota::default()) {
+            limits.push((format!("user '{}'", name), quota, user_usage(root, uid)));
+        }
+    }
+    directory_limits(root, parent_path, 0, &mut limits);
+    check_limits(limits, added)
+}
+
+pub fn check_move(root: &Inode, source_parent_path: &[String], destination_path: &[String], added: Usage) -> Result<Vec<String>, String> {
+    // Moving content only adds to the directories containing the destination but not the source, and leaves
+    // the owner unchanged
+    let common = source_parent_path.iter().zip(destination_path).take_while(|(source, destination)| source == destination).count();
+    let mut limits: Vec<(String, Quota, Usage)> = Vec::new();
+    directory_limits(root, destination_path, common + 1, &mut limits);
+    check_limits(limits, added)
+}
+
+fn check_limits(limits: Vec<(String, Quota, Usage)>, added: Usage) -> Result<Vec<String>, String> {
+    let mut warnings: Vec<String> = Vec::new();
+    for (owner, quota, usage) in limits {
+        let bytes = usage.bytes + added.bytes;
+        let inodes = usage.inodes + added.inodes;
+        if exceeds(bytes, quota.hard_bytes) || exceeds(inodes, quota.hard_inodes) {
+            return Err(format!("Disk quota exceeded for {}", owner));
+        }
+        if (added.bytes > 0 && exceeds(bytes, quota.soft_bytes)) || (added.inodes > 0 && exceeds(inodes, quota.soft_inodes)) {
+            warnings.push(format!("Warning: soft quota exceeded for {}", owner));
+        }
+    }
+    Ok(warnings)
+}
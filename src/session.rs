@@ -0,0 +1,343 @@
+use crate::commands::Registers;
+use crate::line_editor::read_password;
+use crate::quota::{self, Usage};
+use crate::types::{AclTag, Inode, EXECUTE, READ, STICKY, WRITE};
+use crate::users::{self, User};
+use crate::utils;
+
+pub const ROOT_UID: u32 = 0;
+pub const ROOT_GID: u32 = 0;
+
+pub struct Session {
+    pub user: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub groups: Vec<u32>,
+    pub previous: Option<Box<Session>>, // session to go back to on logout
+    pub passphrase: Option<String>,     // key of encrypted files, kept once it has decrypted one
+    pub registers: Registers,           // registers of the editor, kept from one file to the next
+    pub warnings: Vec<String>,          // soft quota warnings, printed by the shell once the command is done
+}
+
+impl Session {
+    pub fn root() -> Session {
+        Session {
+            user: String::from("root"),
+            uid: ROOT_UID,
+            gid: ROOT_GID,
+            groups: vec![ROOT_GID],
+            previous: None,
+            passphrase: None,
+            registers: Registers::default(),
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn for_user(user: &User, groups: Vec<u32>) -> Session {
+        Session {
+            user: user.name.clone(),
+            uid: user.uid,
+            gid: user.gid,
+            groups,
+            previous: None,
+            passphrase: None,
+            registers: Registers::default(),
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn passphrase(&self) -> Result<String, &'static str> {
+        // Passphrase of the session, asked to the user until one has been kept after decrypting a file
+        if let Some(passphrase) = &self.passphrase {
+            return Ok(passphrase.clone());
+        }
+        let passphrase = read_password("Encryption passphrase: ").ok_or("Passphrase not provided")?;
+        if passphrase.is_empty() {
+            return Err("Passphrase not provided");
+        }
+        Ok(passphrase)
+    }
+
+    pub fn new_passphrase(&self) -> Result<String, &'static str> {
+        // Passphrase to encrypt a file with, typed twice unless the session already has a good one
+        if self.passphrase.is_some() {
+            return self.passphrase();
+        }
+        let passphrase = self.passphrase()?;
+        let retyped = read_password("Retype encryption passphrase: ").ok_or("Passphrase not provided")?;
+        if passphrase != retyped {
+            return Err("Passphrases do not match");
+        }
+        Ok(passphrase)
+    }
+
+    pub fn can_sudo(&self, root: &Inode) -> bool {
+        // Whether the user of the session may act as root, being a member of the sudo group
+        users::find_group(root, users::SUDO_GROUP).is_some_and(|group| self.in_group(group.gid) || group.members.contains(&self.user))
+    }
+
+    pub fn is_logged_in(&self, name: &str) -> bool {
+        // Whether the user owns this session or one of the sessions it will go back to
+        self.user == name || self.previous.as_ref().is_some_and(|previous| previous.is_logged_in(name))
+    }
+
+    pub fn in_group(&self, gid: u32) -> bool {
+        self.gid == gid || self.groups.contains(&gid)
+    }
+
+    pub fn can_access(&self, inode: &Inode, access: u16) -> bool {
+        // Check the rwx bits of the class (owner, group or others) the session user falls in, following the
+        // POSIX ACL order: owner, named user entries, owner group and named group entries, then others.
+        // The superuser is granted every access, protection attributes are checked apart and still apply to it
+        if self.uid == ROOT_UID {
+            return true;
+        }
+        let permissions = inode.get_permissions();
+        if inode.get_uid() == self.uid {
+            return (permissions >> 6) & access == access;
+        }
+        let acl = inode.get_acl();
+        let mask = inode.acl_mask();
+        if let Some(entry) = acl.iter().find(|entry| entry.tag == AclTag::User(self.uid)) {
+            return entry.permissions & mask & access == access;
+        }
+        let mut group_matched = false;
+        if self.in_group(inode.get_gid()) {
+            group_matched = true;
+            if (permissions >> 3) & mask & access == access {
+                return true;
+            }
+        }
+        for entry in acl {
+            if let AclTag::Group(gid) = entry.tag {
+                if self.in_group(gid) {
+                    group_matched = true;
+                    if entry.permissions & mask & access == access {
+                        return true;
+                    }
+                }
+            }
+        }
+        if group_matched {
+            return false;
+        }
+        permissions & access == access
+    }
+
+    pub fn check_access(&self, inode: &Inode, access: u16) -> Result<(), &'static str> {
+        if self.can_access(inode, access) {
+            Ok(())
+        } else {
+            Err("Permission denied")
+        }
+    }
+
+    pub fn resolve<'a>(&self, root: &'a Inode, components: &[String]) -> Result<&'a Inode, &'static str> {
+        // Walk the path from root, requiring search (execute) permission on every directory crossed
+        let mut inode = root;
+        for component in components {
+            if !inode.is_directory() {
+                return Err("File or directory not found");
+            }
+            self.check_access(inode, EXECUTE)?;
+            inode = inode.get_children().iter().find(|child| child.get_name() == component).ok_or("File or directory not found")?;
+        }
+        Ok(inode)
+    }
+
+    pub fn resolve_mut<'a>(&self, root: &'a mut Inode, components: &[String]) -> Result<&'a mut Inode, &'static str> {
+        let mut inode = root;
+        for component in components {
+            if !inode.is_directory() {
+                return Err("File or directory not found");
+            }
+            self.check_access(inode, EXECUTE)?;
+            inode = inode.get_inode_by_name_mut(component).ok_or("File or directory not found")?;
+        }
+        Ok(inode)
+    }
+
+    pub fn resolve_parent_mut<'a>(&self, root: &'a mut Inode, path: &str) -> Result<(&'a mut Inode, String), &'static str> {
+        // Resolve the directory containing the path, returning it with the last name of the path
+        let (parent_path, name) = utils::split_path(path)?;
+        let parent_inode = self.resolve_mut(root, &parent_path)?;
+        if !parent_inode.is_directory() {
+            return Err("Not a directory");
+        }
+        Ok((parent_inode, name))
+    }
+
+    pub fn check_create(&self, directory: &Inode, path: &str) -> Result<(), String> {
+        // Adding an entry to a directory needs write and search permission on it, and the directory not to be protected
+        self.check_access(directory, WRITE | EXECUTE)?;
+        directory.check_protected(path)
+    }
+
+    pub fn check_unlink(&self, directory: &Inode, inode: &Inode, path: &str) -> Result<(), String> {
+        // Taking an entry out of its directory, to remove or rename it, needs write and search permission on the
+        // directory, and the sticky bit restricts it to the owners of the entry or of the directory (or root).
+        // Protected entries can't be taken out
+        self.check_access(directory, WRITE | EXECUTE)?;
+        if directory.get_permissions() & STICKY != 0 && self.uid != ROOT_UID && inode.get_uid() != self.uid && directory.get_uid() != self.uid {
+            return Err(String::from("Permission denied"));
+        }
+        inode.check_protected(path)
+    }
+
+    pub fn check_remove(&self, directory: &Inode, inode: &Inode, path: &str) -> Result<(), String> {
+        // Removing an entry also needs everything in it to be removable, for a directory
+        self.check_unlink(directory, inode, path)?;
+        if inode.is_directory() {
+            self.check_access(inode, READ)?;
+            for child in inode.get_children() {
+                self.check_remove(inode, child, &format!("{}/{}", path.trim_end_matches('/'), child.get_name()))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_file(&mut self, root: &Inode, path: &str) -> Result<String, String> {
+        // Content of the file at the path, checking read permission and decrypting it if needed
+        let inode = self.resolve(root, &utils::normalize_path(path))?;
+        self.check_access(inode, READ)?;
+        if inode.is_encrypted() {
+            let passphrase = self.passphrase()?;
+            let content = inode.read_content(Some(&passphrase)).map_err(|error| format!("{} for '{}'", error, path))?;
+            self.passphrase = Some(passphrase);
+            return Ok(content);
+        }
+        Ok(inode.read_content(None)?)
+    }
+
+    pub fn write_file(&mut self, root: &mut Inode, path: &str, data: &str, append: bool) -> Result<(), String> {
+        // Write a file at the path, checking write permission on the file or on its directory when creating it.
+        // Encrypted files stay encrypted. Quotas are computed before borrowing the directory, and reported after
+        // the permission checks
+        let (parent_path, _) = utils::split_path(path)?;
+        let quota = match root.get_inode_by_path(&utils::normalize_path(path)) {
+            Some(inode) => {
+                let bytes = if append { data.len() as u64 } else { (data.len() as u64).saturating_sub(inode.content_size()) };
+                quota::check(root, inode.get_uid(), &parent_path, Usage { bytes, inodes: 0 })
+            },
+            None => quota::check(root, self.uid, &parent_path, Usage { bytes: data.len() as u64, inodes: 1 }),
+        };
+        let (parent_inode, name) = self.resolve_parent_mut(root, path)?;
+        match parent_inode.get_inode_by_name(&name) {
+            Some(inode) => {
+                self.check_access(&inode, WRITE)?;
+                if append {
+                    inode.check_appendable(path)?;
+                } else {
+                    inode.check_protected(path)?;
+                }
+                self.warnings.extend(quota?);
+                if inode.is_encrypted() {
+                    let passphrase = self.passphrase()?;
+                    let mut content = inode.read_content(Some(&passphrase)).map_err(|error| format!("{} for '{}'", error, path))?;
+                    self.passphrase = Some(passphrase.clone());
+                    if !append {
+                        content.clear();
+                    }
+                    content.push_str(data);
+                    let result = parent_inode.update_child(&name, |inode| inode.set_encrypted_content(&passphrase, &content)).unwrap();
+                    root.refresh_sizes(&parent_path);
+                    return Ok(result?);
+                }
+            },
+            None => {
+                self.check_create(parent_inode, &utils::parent_path(path))?;
+                self.warnings.extend(quota?);
+            },
+        }
+        let result = parent_inode.write_file(&name, data, append, self.uid, self.gid);
+        root.refresh_sizes(&parent_path);
+        Ok(result?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Session;
+    use crate::types::{Inode, DIR_MODE, EXECUTE, IMMUTABLE, READ, STICKY, WRITE};
+    use crate::users::User;
+
+    fn session(uid: u32, groups: Vec<u32>) -> Session {
+        let user = User {
+            name: format!("user{}", uid),
+            uid,
+            gid: uid,
+            home: String::new(),
+        };
+        Session::for_user(&user, groups)
+    }
+
+    fn directory(uid: u32, permissions: u16) -> Inode {
+        let mut directory = Inode::new(DIR_MODE, String::from("d"), uid, uid);
+        directory.set_permissions(permissions);
+        directory
+    }
+
+    #[test]
+    fn access_follows_the_class_of_the_user() {
+        let mut file = Inode::new_file_with_data(String::from("f"), String::new(), 1000, 1000);
+        file.set_permissions(0o640);
+        assert!(session(1000, vec![1000]).can_access(&file, READ | WRITE));
+        assert!(!session(1000, vec![1000]).can_access(&file, EXECUTE));
+        assert!(session(2000, vec![2000, 1000]).can_access(&file, READ));
+        assert!(!session(2000, vec![2000, 1000]).can_access(&file, WRITE));
+        assert!(!session(3000, vec![3000]).can_access(&file, READ));
+        assert!(Session::root().can_access(&file, READ | WRITE | EXECUTE));
+
+        // The owner class applies even when it gives less than the others
+        file.set_permissions(0o074);
+        assert!(!session(1000, vec![1000]).can_access(&file, READ));
+        assert!(session(3000, vec![3000]).can_access(&file, READ));
+    }
+
+    #[test]
+    fn creating_needs_write_and_search_permission() {
+        assert!(session(1000, vec![1000]).check_create(&directory(1000, 0o700), "/d").is_ok());
+        assert!(session(1000, vec![1000]).check_create(&directory(1000, 0o600), "/d").is_err());
+        assert!(session(1000, vec![1000]).check_create(&directory(1000, 0o500), "/d").is_err());
+        assert!(session(2000, vec![2000]).check_create(&directory(1000, 0o755), "/d").is_err());
+        assert!(session(2000, vec![2000]).check_create(&directory(1000, 0o777), "/d").is_ok());
+
+        // Root bypasses the permissions, but not the protection attributes
+        let mut protected = directory(1000, 0o777);
+        protected.set_attribute(IMMUTABLE, true);
+        assert!(Session::root().check_create(&directory(1000, 0o000), "/d").is_ok());
+        assert!(Session::root().check_create(&protected, "/d").is_err());
+    }
+
+    #[test]
+    fn sticky_bit_restricts_removal_to_owners() {
+        let mut shared = directory(0, 0o777 | STICKY);
+        shared.add_inode(Inode::new_file_with_data(String::from("f"), String::new(), 1000, 1000));
+        let file = shared.get_inode_by_name("f").unwrap();
+        assert!(session(1000, vec![1000]).check_remove(&shared, &file, "/d/f").is_ok());
+        assert!(session(2000, vec![2000]).check_remove(&shared, &file, "/d/f").is_err());
+        assert!(Session::root().check_remove(&shared, &file, "/d/f").is_ok());
+
+        // The owner of the directory can remove anything in it, and without the sticky bit anyone with write permission can
+        let mut owned = directory(2000, 0o777 | STICKY);
+        owned.add_inode(file.clone());
+        assert!(session(2000, vec![2000]).check_remove(&owned, &file, "/d/f").is_ok());
+        shared.set_permissions(0o777);
+        assert!(session(2000, vec![2000]).check_remove(&shared, &file, "/d/f").is_ok());
+        shared.set_permissions(0o755);
+        assert!(session(2000, vec![2000]).check_remove(&shared, &file, "/d/f").is_err());
+    }
+
+    #[test]
+    fn removing_a_directory_checks_its_content() {
+        let mut parent = directory(1000, 0o755);
+        let mut child = directory(1000, 0o777);
+        let mut file = Inode::new_file_with_data(String::from("f"), String::new(), 1000, 1000);
+        file.set_attribute(IMMUTABLE, true);
+        child.add_inode(file);
+        parent.add_inode(child);
+        let child = parent.get_inode_by_name("d").unwrap();
+        assert!(session(1000, vec![1000]).check_remove(&parent, &child, "/p/d").is_err());
+        assert!(Session::root().check_remove(&parent, &child, "/p/d").is_err());
+    }
+}
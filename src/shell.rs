@@ -0,0 +1,221 @@
+use std::io::{empty, stdout, Read};
+use crate::audit;
+use crate::commands_handler::{handle_commands, print_warnings};
+use crate::session::Session;
+use crate::types::Inode;
+
+enum Token {
+    Word(String),
+    RedirectOut,
+    RedirectAppend,
+    Pipe,
+}
+
+struct Redirect {
+    name: String,
+    append: bool,
+}
+
+struct Pipeline {
+    commands: Vec<Vec<String>>,
+    redirect: Option<Redirect>,
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>, &'static str> {
+    // Split the line in words, keeping quoted text together and isolating the redirection and pipe operators
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => word.push(c),
+            None => match c {
+                '"' | '\'' => {
+                    quote = Some(c);
+                    in_word = true;
+                },
+                '|' => {
+                    if in_word {
+                        tokens.push(Token::Word(std::mem::take(&mut word)));
+                        in_word = false;
+                    }
+                    tokens.push(Token::Pipe);
+                },
+                '>' => {
+                    if in_word {
+                        tokens.push(Token::Word(std::mem::take(&mut word)));
+                        in_word = false;
+                    }
+                    if chars.peek() == Some(&'>') {
+                        chars.next();
+                        tokens.push(Token::RedirectAppend);
+                    } else {
+                        tokens.push(Token::RedirectOut);
+                    }
+                },
+                c if c.is_whitespace() => {
+                    if in_word {
+                        tokens.push(Token::Word(std::mem::take(&mut word)));
+                        in_word = false;
+                    }
+                },
+                _ => {
+                    word.push(c);
+                    in_word = true;
+                },
+            },
+        }
+    }
+    if quote.is_some() {
+        return Err("Unterminated quote in command");
+    }
+    if in_word {
+        tokens.push(Token::Word(word));
+    }
+    Ok(tokens)
+}
+
+fn parse_pipeline(line: &str) -> Result<Option<Pipeline>, &'static str> {
+    let mut commands: Vec<Vec<String>> = vec![Vec::new()];
+    let mut redirect: Option<Redirect> = None;
+    let mut tokens = tokenize(line)?.into_iter();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Word(word) => {
+                if redirect.is_some() {
+                    return Err("Redirection must be at the end of the command");
+                }
+                commands.last_mut().unwrap().push(word);
+            },
+            Token::Pipe => {
+                if redirect.is_some() {
+                    return Err("Redirection must be at the end of the command");
+                }
+                if commands.last().unwrap().is_empty() {
+                    return Err("Missing command in pipeline");
+                }
+                commands.push(Vec::new());
+            },
+            Token::RedirectOut | Token::RedirectAppend => {
+                let append = matches!(token, Token::RedirectAppend);
+                let name = match tokens.next() {
+                    Some(Token::Word(name)) => name,
+                    _ => return Err("Missing file name for redirection"),
+                };
+                if redirect.is_some() {
+                    return Err("Only one output redirection is allowed");
+                }
+                redirect = Some(Redirect { name, append });
+            },
+        }
+    }
+    if commands.len() == 1 && commands[0].is_empty() {
+        if redirect.is_some() {
+            return Err("Missing command before redirection");
+        }
+        return Ok(None);
+    }
+    if commands.iter().any(|command| command.is_empty()) {
+        return Err("Missing command in pipeline");
+    }
+    Ok(Some(Pipeline { commands, redirect }))
+}
+
+pub fn run_command_line(line: &str, actual_inode: &mut Inode, session: &mut Session) -> bool {
+    // Parse and run one line typed in the REPL, returning true when the program should quit
+    let pipeline = match parse_pipeline(line) {
+        Ok(Some(pipeline)) => pipeline,
+        Ok(None) => return false,
+        Err(error) => {
+            eprintln!("{}", error);
+            return false;
+        }
+    };
+
+    // Every command but the last writes into a buffer that becomes the input of the next one
+    let mut quit = false;
+    let mut input: Box<dyn Read> = Box::new(empty());
+    let last = pipeline.commands.len() - 1;
+    for (index, command) in pipeline.commands.into_iter().enumerate() {
+        if index == last && pipeline.redirect.is_none() {
+            quit |= handle_commands(command, actual_inode, session, &mut input, &mut stdout());
+            break;
+        }
+        let mut buffer: Vec<u8> = Vec::new();
+        quit |= handle_commands(command, actual_inode, session, &mut input, &mut buffer);
+        if index == last {
+            if let Some(redirect) = &pipeline.redirect {
+                let data = String::from_utf8_lossy(&buffer);
+                let operator = if redirect.append { ">>" } else { ">" };
+                let user = session.user.clone();
+                let result = audit::audited(actual_inode, &user, &[operator.to_string(), redirect.name.clone()], |actual_inode| {
+                    session.write_file(actual_inode, &redirect.name, &data, redirect.append)
+                });
+                if let Err(error) = result {
+                    eprintln!("{}", error);
+                }
+                print_warnings(session);
+            }
+        } else {
+            input = Box::new(std::io::Cursor::new(buffer));
+        }
+    }
+    quit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_pipeline, tokenize, Token};
+
+    fn tokens(line: &str) -> Vec<String> {
+        // Words as they are, and the operators as they are typed
+        tokenize(line)
+            .unwrap()
+            .into_iter()
+            .map(|token| match token {
+                Token::Word(word) => word,
+                Token::RedirectOut => String::from(">"),
+                Token::RedirectAppend => String::from(">>"),
+                Token::Pipe => String::from("|"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn quotes_keep_words_together() {
+        assert_eq!(tokens("write  a.txt \"hello world\""), ["write", "a.txt", "hello world"]);
+        assert_eq!(tokens("echo 'a \"b\" | c'"), ["echo", "a \"b\" | c"]);
+        assert_eq!(tokens("echo pre\"fix suf\"fix ''"), ["echo", "prefix suffix", ""]);
+        assert!(tokenize("echo \"open").is_err());
+    }
+
+    #[test]
+    fn operators_split_words() {
+        assert_eq!(tokens("cat a|grep x>out"), ["cat", "a", "|", "grep", "x", ">", "out"]);
+        assert_eq!(tokens("echo x >>log"), ["echo", "x", ">>", "log"]);
+        assert_eq!(tokens("echo '>' \"|\""), ["echo", ">", "|"]);
+    }
+
+    #[test]
+    fn pipelines_are_parsed() {
+        let pipeline = parse_pipeline("cat a | grep x | wc -l >> count").unwrap().unwrap();
+        assert_eq!(pipeline.commands, [vec!["cat", "a"], vec!["grep", "x"], vec!["wc", "-l"]]);
+        let redirect = pipeline.redirect.unwrap();
+        assert_eq!((redirect.name.as_str(), redirect.append), ("count", true));
+        assert!(!parse_pipeline("echo x > out").unwrap().unwrap().redirect.unwrap().append);
+        assert!(parse_pipeline("wc").unwrap().unwrap().redirect.is_none());
+        assert!(parse_pipeline("   ").unwrap().is_none());
+    }
+
+    #[test]
+    fn invalid_pipelines_are_rejected() {
+        for line in ["| wc", "cat a |", "cat a || wc", "echo x >", "echo x > a > b", "echo x > a | wc", "echo x > a b", "> a"] {
+            assert!(parse_pipeline(line).is_err(), "{}", line);
+        }
+    }
+}
@@ -1,11 +1,54 @@
 use std::mem::size_of;
+use super::crypto::{self, EncryptedData};
 use super::utils;
 pub const DIR_MODE: u8 = 0;
 pub const FILE_MODE: u8 = 1;
 
-const ROOT_INODE: u64 = 0;
+pub const SET_UID: u16 = 0o4000;
+pub const SET_GID: u16 = 0o2000;
+pub const STICKY: u16 = 0o1000;
+pub const READ: u16 = 0o4;
+pub const WRITE: u16 = 0o2;
+pub const EXECUTE: u16 = 0o1;
+pub const DEFAULT_DIR_PERMISSIONS: u16 = 0o755;
+pub const DEFAULT_FILE_PERMISSIONS: u16 = 0o644;
+pub const BLOCK_SIZE: u64 = 1024;
+
+pub const READ_ONLY: u8 = 0b01;             // content can't change, set by the owner
+pub const IMMUTABLE: u8 = 0b10;             // nothing can change, set by root
+pub const APPEND_ONLY: u8 = 0b100;          // content can only grow, set on system logs
+
+const DIRECTORY_BLOCKS: u64 = 1;            // a directory takes one block for its entries
+
+fn data_blocks(length: usize) -> u64 {
+    (length as u64).div_ceil(BLOCK_SIZE)
+}
+
 static mut INODE_SERIAL_NUMER: u64 = 0;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AclTag {
+    User(u32),
+    Group(u32),
+    Mask,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AclEntry {
+    pub tag: AclTag,
+    pub permissions: u16, // rwx bits
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Quota {
+    // Limits on the content bytes and the number of inodes, 0 meaning no limit. Going over the soft
+    // limits only gives a warning, the hard limits can't be exceeded
+    pub soft_bytes: u64,
+    pub hard_bytes: u64,
+    pub soft_inodes: u64,
+    pub hard_inodes: u64,
+}
+
 #[derive(Debug, Clone)]
 pub enum InodeData {
     File(File),
@@ -15,8 +58,15 @@ pub enum InodeData {
 #[derive(Debug, Clone)]
 pub struct Inode {
     mode: u8,                  // file or directory
-    size: u64,                 // in bytes
-    permissions: (bool, bool), // (read, write)
+    size: u64,                 // in bytes, with the content of directories
+    blocks: u64,               // allocated blocks of BLOCK_SIZE bytes, with the content of directories
+    uid: u32,                  // owner user
+    gid: u32,                  // owner group
+    permissions: u16,          // setuid, setgid, sticky and rwx bits for owner, group and others
+    attributes: u8,            // read-only and immutable flags
+    acl: Vec<AclEntry>,        // extra entries for named users and groups, limited by the mask entry
+    default_acl: Vec<AclEntry>, // entries inherited by the children of a directory
+    quota: Option<Quota>,      // limits on the content of a directory subtree
     created_at: Option<u64>,
     updated_at: Option<u64>,
     accessed_at: Option<u64>,
@@ -25,7 +75,7 @@ pub struct Inode {
 }
 
 impl Inode {
-    pub fn new(mode: u8, name: String) -> Inode {
+    pub fn new(mode: u8, name: String, uid: u32, gid: u32) -> Inode {
         let serial_number: u64 = unsafe { INODE_SERIAL_NUMER };
         unsafe { INODE_SERIAL_NUMER += 1; }
         if mode == DIR_MODE {
@@ -33,7 +83,14 @@ impl Inode {
             Inode {
                 mode,
                 size,
-                permissions: (true, true),
+                blocks: DIRECTORY_BLOCKS,
+                uid,
+                gid,
+                permissions: DEFAULT_DIR_PERMISSIONS,
+                attributes: 0,
+                acl: Vec::new(),
+                default_acl: Vec::new(),
+                quota: None,
                 created_at: Some(utils::now_date()),
                 updated_at: Some(utils::now_date()),
                 accessed_at: Some(utils::now_date()),
@@ -45,7 +102,14 @@ impl Inode {
             Inode {
                 mode,
                 size,
-                permissions: (true, true),
+                blocks: 0,
+                uid,
+                gid,
+                permissions: DEFAULT_FILE_PERMISSIONS,
+                attributes: 0,
+                acl: Vec::new(),
+                default_acl: Vec::new(),
+                quota: None,
                 created_at: Some(utils::now_date()),
                 updated_at: Some(utils::now_date()),
                 accessed_at: Some(utils::now_date()),
@@ -55,14 +119,21 @@ impl Inode {
         }
     }
 
-    pub fn new_file_with_data(name: String, data: String) -> Inode {
+    pub fn new_file_with_data(name: String, data: String, uid: u32, gid: u32) -> Inode {
         let size = (size_of::<Inode>() + size_of::<File>() + data.len()) as u64;
         let serial_number: u64 = unsafe { INODE_SERIAL_NUMER };
         unsafe { INODE_SERIAL_NUMER += 1; }
         Inode {
             mode: FILE_MODE,
             size,
-            permissions: (true, true),
+            blocks: data_blocks(data.len()),
+            uid,
+            gid,
+            permissions: DEFAULT_FILE_PERMISSIONS,
+            attributes: 0,
+            acl: Vec::new(),
+            default_acl: Vec::new(),
+            quota: None,
             created_at: Some(utils::now_date()),
             updated_at: Some(utils::now_date()),
             accessed_at: Some(utils::now_date()),
@@ -72,27 +143,17 @@ impl Inode {
     }
 
     pub fn remove_inode(&mut self, rem_inode: Inode) {
-        if self.is_directory() {
-            self.size -= rem_inode.size;
-            match &mut self.data {
-                InodeData::Directory(directory) => {
-                    let mut index = 0;
-                    for (i, child_inode) in directory.files.iter().enumerate() {
-                        if rem_inode.serial_number == child_inode.serial_number {
-                            match &rem_inode.data {
-                                InodeData::Directory(directory) => {
-                                    directory.clone().recursive_remove();
-                                },
-                                _ => {}
-                            }
-                            index = i;
-                            break;
-                        }
-                    }
-                    directory.files.remove(index);
-                },
-                _ => eprintln!("Error: trying to remove a file from a non-directory inode"),
-            }
+        match &mut self.data {
+            InodeData::Directory(directory) => {
+                // Dropping the entry drops everything under it. Nothing is removed when no child matches
+                let Some(index) = directory.files.iter().position(|child_inode| child_inode.serial_number == rem_inode.serial_number) else {
+                    return;
+                };
+                directory.files.remove(index);
+                self.size = self.size.saturating_sub(rem_inode.size);
+                self.blocks = self.blocks.saturating_sub(rem_inode.blocks);
+            },
+            _ => eprintln!("Error: trying to remove a file from a non-directory inode"),
         }
     }
 
@@ -100,7 +161,14 @@ impl Inode {
         Inode {
             mode: self.mode,
             size: self.size,
+            blocks: self.blocks,
+            uid: self.uid,
+            gid: self.gid,
             permissions: self.permissions,
+            attributes: self.attributes,
+            acl: self.acl.clone(),
+            default_acl: self.default_acl.clone(),
+            quota: self.quota,
             created_at: self.created_at,
             updated_at: self.updated_at,
             accessed_at: self.accessed_at,
@@ -123,6 +191,131 @@ impl Inode {
         self.size
     }
 
+    pub fn get_blocks(&self) -> u64 {
+        self.blocks
+    }
+
+    pub fn get_serial_number(&self) -> u64 {
+        self.serial_number
+    }
+
+    pub fn get_uid(&self) -> u32 {
+        self.uid
+    }
+
+    pub fn get_gid(&self) -> u32 {
+        self.gid
+    }
+
+    pub fn get_permissions(&self) -> u16 {
+        self.permissions
+    }
+
+    pub fn set_permissions(&mut self, permissions: u16) {
+        self.permissions = permissions & 0o7777;
+        self.updated_at = Some(utils::now_date());
+    }
+
+    pub fn set_owner(&mut self, uid: u32, gid: u32) {
+        self.uid = uid;
+        self.gid = gid;
+        self.updated_at = Some(utils::now_date());
+    }
+
+    pub fn get_acl(&self) -> &[AclEntry] {
+        &self.acl
+    }
+
+    pub fn get_default_acl(&self) -> &[AclEntry] {
+        &self.default_acl
+    }
+
+    pub fn set_acl(&mut self, acl: Vec<AclEntry>) {
+        self.acl = acl;
+        self.updated_at = Some(utils::now_date());
+    }
+
+    pub fn set_default_acl(&mut self, default_acl: Vec<AclEntry>) {
+        self.default_acl = default_acl;
+        self.updated_at = Some(utils::now_date());
+    }
+
+    pub fn acl_mask(&self) -> u16 {
+        // Upper limit of the permissions given by named entries and by the owner group
+        match self.acl.iter().find(|entry| entry.tag == AclTag::Mask) {
+            Some(entry) => entry.permissions,
+            None => 0o7,
+        }
+    }
+
+    pub fn get_quota(&self) -> Option<Quota> {
+        self.quota
+    }
+
+    pub fn set_quota(&mut self, quota: Option<Quota>) {
+        self.quota = quota;
+    }
+
+    pub fn has_attribute(&self, attribute: u8) -> bool {
+        self.attributes & attribute != 0
+    }
+
+    pub fn set_attribute(&mut self, attribute: u8, enabled: bool) {
+        if enabled {
+            self.attributes |= attribute;
+        } else {
+            self.attributes &= !attribute;
+        }
+        self.updated_at = Some(utils::now_date());
+    }
+
+    pub fn check_protected(&self, path: &str) -> Result<(), String> {
+        // Fail, naming the path, if the inode is read-only, immutable or append-only
+        self.check_appendable(path)?;
+        if self.has_attribute(APPEND_ONLY) {
+            return Err(format!("'{}' is append-only", path));
+        }
+        Ok(())
+    }
+
+    pub fn check_appendable(&self, path: &str) -> Result<(), String> {
+        // Fail, naming the path, if nothing can be added to the content of the inode
+        if self.has_attribute(IMMUTABLE) {
+            Err(format!("'{}' is immutable", path))
+        } else if self.has_attribute(READ_ONLY) {
+            Err(format!("'{}' is read-only", path))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        match &mut self.data {
+            InodeData::File(file) => file.name = name,
+            InodeData::Directory(directory) => directory.name = name,
+        }
+        self.updated_at = Some(utils::now_date());
+    }
+
+    pub fn permissions_string(&self) -> String {
+        // Permissions in the format of 'ls -l', like drwxr-xr-x
+        let mut result = String::from(if self.is_directory() { "d" } else { "-" });
+        let specials = [(SET_UID, 's'), (SET_GID, 's'), (STICKY, 't')];
+        for (index, (special, special_char)) in specials.iter().enumerate() {
+            let bits = (self.permissions >> (6 - 3 * index)) & 0o7;
+            result.push(if bits & READ != 0 { 'r' } else { '-' });
+            result.push(if bits & WRITE != 0 { 'w' } else { '-' });
+            let execute = bits & EXECUTE != 0;
+            result.push(match (self.permissions & special != 0, execute) {
+                (true, true) => *special_char,
+                (true, false) => special_char.to_ascii_uppercase(),
+                (false, true) => 'x',
+                (false, false) => '-',
+            });
+        }
+        result
+    }
+
     pub fn is_file(&self) -> bool {
         self.mode == FILE_MODE
     }
@@ -131,19 +324,237 @@ impl Inode {
         self.mode == DIR_MODE
     }
 
-    pub fn add_inode(&mut self, inode: Inode) {
+    pub fn add_inode(&mut self, mut inode: Inode) {
         if self.is_directory() {
-            self.size += inode.size;
-            match &mut self.data {
-                InodeData::Directory(directory) => directory.add_inode(inode),
-                _ => eprintln!("Error: trying to add a file to a non-directory inode"),
+            // Like in unix, the setgid bit of a directory makes its children inherit its group
+            if self.permissions & SET_GID != 0 {
+                inode.gid = self.gid;
+                if inode.is_directory() {
+                    inode.permissions |= SET_GID;
+                }
             }
+            // Children get the default ACL of the directory, and directories pass it on. As in POSIX, the group
+            // bits of the mode the child is created with limit the mask of the inherited entries
+            if !self.default_acl.is_empty() {
+                let group_permissions = (inode.permissions >> 3) & 0o7;
+                let mut acl = self.default_acl.clone();
+                match acl.iter_mut().find(|entry| entry.tag == AclTag::Mask) {
+                    Some(mask) => mask.permissions &= group_permissions,
+                    None => acl.push(AclEntry { tag: AclTag::Mask, permissions: group_permissions }),
+                }
+                inode.acl = acl;
+                if inode.is_directory() {
+                    inode.default_acl = self.default_acl.clone();
+                }
+            }
+            self.insert_inode(inode);
         } else {
             // todo: handle error
             eprintln!("Error: trying to add a file to a non-directory inode");
         }
     }
 
+    pub fn insert_inode(&mut self, inode: Inode) {
+        // Add the inode to this directory as it is, without inheriting anything from it
+        match &mut self.data {
+            InodeData::Directory(directory) => {
+                self.size += inode.size;
+                self.blocks += inode.blocks;
+                directory.add_inode(inode);
+            },
+            _ => eprintln!("Error: trying to add a file to a non-directory inode"),
+        }
+    }
+
+    pub fn get_data(&self) -> Option<&String> {
+        match &self.data {
+            InodeData::File(file) => Some(&file.data),
+            _ => None,
+        }
+    }
+
+    pub fn get_children(&self) -> &[Inode] {
+        match &self.data {
+            InodeData::Directory(directory) => &directory.files,
+            _ => &[],
+        }
+    }
+
+    pub fn walk(&self, function: &mut dyn FnMut(&Inode)) {
+        // Call the function on this inode and on all its descendants
+        function(self);
+        for child in self.get_children() {
+            child.walk(function);
+        }
+    }
+
+    pub fn walk_mut(&mut self, function: &mut dyn FnMut(&mut Inode)) {
+        // Call the function on this inode and on all its descendants
+        function(self);
+        if let InodeData::Directory(directory) = &mut self.data {
+            for child in &mut directory.files {
+                child.walk_mut(function);
+            }
+        }
+    }
+
+    pub fn get_children_names(&self) -> Vec<String> {
+        self.get_children().iter().map(|inode| inode.get_name().clone()).collect()
+    }
+
+    pub fn get_inode_by_name_mut(&mut self, name: &str) -> Option<&mut Inode> {
+        match &mut self.data {
+            InodeData::Directory(directory) => directory.files.iter_mut().find(|inode| inode.get_name() == name),
+            _ => None,
+        }
+    }
+
+    pub fn get_inode_by_path(&self, components: &[String]) -> Option<&Inode> {
+        let mut inode = self;
+        for component in components {
+            inode = inode.get_children().iter().find(|child| child.get_name() == component)?;
+        }
+        Some(inode)
+    }
+
+    pub fn get_inode_by_path_mut(&mut self, components: &[String]) -> Option<&mut Inode> {
+        let mut inode = self;
+        for component in components {
+            inode = inode.get_inode_by_name_mut(component)?;
+        }
+        Some(inode)
+    }
+
+    pub fn touch(&mut self) {
+        let now = utils::now_date();
+        self.updated_at = Some(now);
+        self.accessed_at = Some(now);
+    }
+
+    pub fn update_child<R>(&mut self, name: &str, function: impl FnOnce(&mut Inode) -> R) -> Option<R> {
+        // Apply the function to the child with the given name, keeping the size of this directory up to date
+        let child = self.get_inode_by_name_mut(name)?;
+        let (previous_size, previous_blocks) = (child.size, child.blocks);
+        let result = function(child);
+        let (new_size, new_blocks) = (child.size, child.blocks);
+        self.size = self.size.saturating_sub(previous_size) + new_size;
+        self.blocks = self.blocks.saturating_sub(previous_blocks) + new_blocks;
+        Some(result)
+    }
+
+    pub fn refresh_sizes(&mut self, components: &[String]) {
+        // Recompute the size of the directories on the path, from the deepest one up to this one, after a change
+        // inside the last directory of the path
+        if let Some((first, rest)) = components.split_first() {
+            if let Some(child) = self.get_inode_by_name_mut(first) {
+                child.refresh_sizes(rest);
+            }
+        }
+        if let InodeData::Directory(directory) = &self.data {
+            self.size = (size_of::<Inode>() + size_of::<Directory>()) as u64 + directory.files.iter().map(|child| child.size).sum::<u64>();
+            self.blocks = DIRECTORY_BLOCKS + directory.files.iter().map(|child| child.blocks).sum::<u64>();
+        }
+    }
+
+    fn refresh_file_size(&mut self) {
+        if let InodeData::File(file) = &self.data {
+            let encrypted_size = file.encrypted.as_ref().map_or(0, EncryptedData::size);
+            self.size = (size_of::<Inode>() + size_of::<File>() + file.data.len() + encrypted_size) as u64;
+            self.blocks = data_blocks(file.data.len() + encrypted_size);
+        }
+    }
+
+    pub fn content_size(&self) -> u64 {
+        // Bytes of content stored for a file, encrypted or not, without the metadata
+        match &self.data {
+            InodeData::File(file) => (file.data.len() + file.encrypted.as_ref().map_or(0, EncryptedData::size)) as u64,
+            InodeData::Directory(_) => 0,
+        }
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        matches!(&self.data, InodeData::File(file) if file.encrypted.is_some())
+    }
+
+    pub fn set_content(&mut self, data: String) -> Result<(), &'static str> {
+        match &mut self.data {
+            InodeData::File(file) if file.encrypted.is_some() => return Err("The file is encrypted"),
+            InodeData::File(file) => file.data = data,
+            InodeData::Directory(_) => return Err("Cannot write into a directory"),
+        }
+        self.refresh_file_size();
+        self.touch();
+        Ok(())
+    }
+
+    pub fn read_content(&self, passphrase: Option<&str>) -> Result<String, &'static str> {
+        // Content of the file, decrypted with the passphrase when the file is encrypted
+        match &self.data {
+            InodeData::File(file) => match (&file.encrypted, passphrase) {
+                (None, _) => Ok(file.data.clone()),
+                (Some(encrypted), Some(passphrase)) => crypto::decrypt(passphrase, encrypted, &self.serial_number.to_le_bytes()),
+                (Some(_), None) => Err("The file is encrypted and no passphrase was provided"),
+            },
+            InodeData::Directory(_) => Err("Cannot read a directory"),
+        }
+    }
+
+    pub fn set_encrypted_content(&mut self, passphrase: &str, data: &str) -> Result<(), &'static str> {
+        // The serial number is authenticated with the content, so encrypted data can't be swapped between files
+        let encrypted = crypto::encrypt(passphrase, data, &self.serial_number.to_le_bytes());
+        match &mut self.data {
+            InodeData::File(file) => {
+                file.data = String::new();
+                file.encrypted = Some(encrypted);
+            },
+            InodeData::Directory(_) => return Err("Cannot encrypt a directory"),
+        }
+        self.refresh_file_size();
+        self.touch();
+        Ok(())
+    }
+
+    pub fn encrypt(&mut self, passphrase: &str) -> Result<(), &'static str> {
+        if self.is_encrypted() {
+            return Err("The file is already encrypted");
+        }
+        let data = self.read_content(None)?;
+        self.set_encrypted_content(passphrase, &data)
+    }
+
+    pub fn decrypt(&mut self, passphrase: &str) -> Result<(), &'static str> {
+        if !self.is_encrypted() {
+            return Err("The file is not encrypted");
+        }
+        let data = self.read_content(Some(passphrase))?;
+        if let InodeData::File(file) = &mut self.data {
+            file.encrypted = None;
+        }
+        self.set_content(data)
+    }
+
+    pub fn write_file(&mut self, name: &str, data: &str, append: bool, uid: u32, gid: u32) -> Result<(), &'static str> {
+        // Replace (or append to) the content of the file with the given name, creating it owned by uid and gid if needed
+        if !self.is_directory() {
+            return Err("Not a directory, files can only be written inside directories");
+        }
+        let result = self.update_child(name, |inode| {
+            let content = match inode.get_data() {
+                Some(current) if append => current.clone() + data,
+                _ => data.to_string(),
+            };
+            inode.set_content(content)
+        });
+        match result {
+            Some(result) => {
+                result?;
+                self.updated_at = Some(utils::now_date());
+            },
+            None => self.add_inode(Inode::new_file_with_data(name.to_string(), data.to_string(), uid, gid)),
+        }
+        Ok(())
+    }
+
     pub fn get_inode_by_name(&self, name: &str) -> Option<Inode> {
         if self.is_directory() {
             match &self.data {
@@ -167,6 +578,7 @@ impl Inode {
 pub struct File {
     name: String,
     data: String,
+    encrypted: Option<EncryptedData>, // when set, the content is here and data is empty
 }
 
 impl File {
@@ -174,6 +586,7 @@ impl File {
         File {
             name,
             data: String::new(),
+            encrypted: None,
         }
     }
 
@@ -181,6 +594,7 @@ impl File {
         File {
             name,
             data,
+            encrypted: None,
         }
     }
 
@@ -188,6 +602,7 @@ impl File {
         File {
             name: self.name.clone(),
             data: self.data.clone(),
+            encrypted: self.encrypted.clone(),
         }
     }
 }
@@ -216,21 +631,72 @@ impl Directory {
     pub fn add_inode(&mut self, inode: Inode) {
         self.files.push(inode);
     }
+}
 
-    pub fn recursive_remove(&mut self) {
-        let mut index = 0;
-        for inode in self.files.clone() {
-            match &inode.data {
-                InodeData::Directory(child_directory) => {
-                    child_directory.clone().recursive_remove();
-                    self.files.remove(index);
-                },
-                InodeData::File(_) => {
-                    self.files.remove(index);
-                },
-                _ => {},
-            }
-            index += 1;
+#[cfg(test)]
+mod tests {
+    use super::{Inode, DIR_MODE};
+
+    fn path(path: &str) -> Vec<String> {
+        path.split('/').map(String::from).collect()
+    }
+
+    fn expected_size(directory: &Inode) -> (u64, u64) {
+        // Size and blocks of a directory from the ones of its children, as refresh_sizes computes them
+        let empty = Inode::new(DIR_MODE, String::new(), 0, 0);
+        let children = directory.get_children();
+        (
+            empty.get_size() + children.iter().map(Inode::get_size).sum::<u64>(),
+            empty.get_blocks() + children.iter().map(Inode::get_blocks).sum::<u64>(),
+        )
+    }
+
+    #[test]
+    fn sizes_are_refreshed_on_every_ancestor() {
+        let mut root = Inode::new(DIR_MODE, String::from("/"), 0, 0);
+        for (parent, name) in [("", "a"), ("a", "b"), ("a/b", "c")] {
+            let components = if parent.is_empty() { Vec::new() } else { path(parent) };
+            root.get_inode_by_path_mut(&components).unwrap().add_inode(Inode::new(DIR_MODE, String::from(name), 0, 0));
+            root.refresh_sizes(&components);
+        }
+        root.get_inode_by_path_mut(&path("a/b/c")).unwrap().write_file("f", &"x".repeat(3000), false, 0, 0).unwrap();
+        root.refresh_sizes(&path("a/b/c"));
+        for directory in ["a/b/c", "a/b", "a"] {
+            let inode = root.get_inode_by_path_mut(&path(directory)).unwrap();
+            assert_eq!((inode.get_size(), inode.get_blocks()), expected_size(inode), "{}", directory);
         }
+        assert_eq!((root.get_size(), root.get_blocks()), expected_size(&root));
+        assert_eq!(root.get_blocks(), 4 + 3);
+
+        // Removing the file brings every ancestor back to the size of its empty directories
+        let directory = root.get_inode_by_path_mut(&path("a/b/c")).unwrap();
+        let file = directory.get_inode_by_name("f").unwrap();
+        directory.remove_inode(file);
+        root.refresh_sizes(&path("a/b/c"));
+        assert_eq!(root.get_blocks(), 4);
+        assert_eq!((root.get_size(), root.get_blocks()), expected_size(&root));
+    }
+
+    #[test]
+    fn only_the_matching_child_is_removed() {
+        let mut root = Inode::new(DIR_MODE, String::from("/"), 0, 0);
+        let mut directory = Inode::new(DIR_MODE, String::from("d"), 0, 0);
+        for name in ["a", "b", "c"] {
+            directory.add_inode(Inode::new_file_with_data(String::from(name), String::from("x"), 0, 0));
+        }
+        root.add_inode(directory);
+        root.add_inode(Inode::new_file_with_data(String::from("f"), String::from("x"), 0, 0));
+        let (size, blocks) = (root.get_size(), root.get_blocks());
+
+        // An inode that isn't a child leaves the directory as it is
+        root.remove_inode(Inode::new_file_with_data(String::from("f"), String::from("x"), 0, 0));
+        assert_eq!(root.get_children().len(), 2);
+        assert_eq!((root.get_size(), root.get_blocks()), (size, blocks));
+
+        // A directory goes with all its entries
+        let directory = root.get_inode_by_name("d").unwrap();
+        root.remove_inode(directory.clone());
+        assert_eq!(root.get_children_names(), ["f"]);
+        assert_eq!((root.get_size(), root.get_blocks()), (size - directory.get_size(), blocks - directory.get_blocks()));
     }
 }
@@ -0,0 +1,320 @@
+use pbkdf2::pbkdf2_hmac;
+use sha2::Sha256;
+use crate::session::{ROOT_GID, ROOT_UID};
+use crate::types::{Inode, DIR_MODE, STICKY};
+use crate::utils;
+
+pub const PASSWD_PATH: &str = "/etc/passwd";
+pub const SHADOW_PATH: &str = "/etc/shadow";
+pub const GROUP_PATH: &str = "/etc/group";
+pub const HOME_PATH: &str = "/home";
+pub const SUDO_GROUP: &str = "sudo";
+pub const DEFAULT_USER: &str = "user";
+
+const FIRST_USER_ID: u32 = 1000;
+const SUDO_GID: u32 = 27;
+const SALT_LENGTH: usize = 16;
+const HASH_ROUNDS: u32 = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct User {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub home: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Group {
+    pub name: String,
+    pub gid: u32,
+    pub members: Vec<String>,
+}
+
+pub fn bootstrap(root: &mut Inode) {
+    // Create the system directories and the user database with the root account, and the unprivileged
+    // account the shell starts with, allowed to run commands as root through sudo
+    for (path, permissions) in [("etc", 0o755), ("home", 0o755), ("root", 0o700), ("tmp", 0o777 | STICKY), ("var", 0o755), ("var/log", 0o755)] {
+        let (parent_path, name) = utils::split_path(path).expect("Invalid system directory path");
+        let mut directory = Inode::new(DIR_MODE, name, ROOT_UID, ROOT_GID);
+        directory.set_permissions(permissions);
+        root.get_inode_by_path_mut(&parent_path).expect("System directory not found").add_inode(directory);
+        root.refresh_sizes(&parent_path);
+    }
+    let user = User {
+        name: String::from(DEFAULT_USER),
+        uid: FIRST_USER_ID,
+        gid: FIRST_USER_ID,
+        home: format!("{}/{}", HOME_PATH, DEFAULT_USER),
+    };
+    let mut home_directory = Inode::new(DIR_MODE, String::from(DEFAULT_USER), user.uid, user.gid);
+    home_directory.set_permissions(0o700);
+    root.get_inode_by_path_mut(&utils::normalize_path(HOME_PATH)).unwrap().add_inode(home_directory);
+    root.refresh_sizes(&utils::normalize_path(HOME_PATH));
+    write_users(root, &[
+        User {
+            name: String::from("root"),
+            uid: ROOT_UID,
+            gid: ROOT_GID,
+            home: String::from("/root"),
+        },
+        user,
+    ]);
+    write_groups(root, &[
+        Group {
+            name: String::from("root"),
+            gid: ROOT_GID,
+            members: Vec::new(),
+        },
+        Group {
+            name: String::from(SUDO_GROUP),
+            gid: SUDO_GID,
+            members: vec![String::from(DEFAULT_USER)],
+        },
+        Group {
+            name: String::from(DEFAULT_USER),
+            gid: FIRST_USER_ID,
+            members: Vec::new(),
+        },
+    ]);
+    // root is locked, and the default user has no password until one is set with passwd
+    write_system_file(root, SHADOW_PATH, format!("root:!:\n{}::\n", DEFAULT_USER), 0o600);
+}
+
+pub fn read_system_file(root: &Inode, path: &str) -> String {
+    match root.get_inode_by_path(&utils::normalize_path(path)).and_then(Inode::get_data) {
+        Some(data) => data.clone(),
+        None => String::new(),
+    }
+}
+
+pub fn write_system_file(root: &mut Inode, path: &str, data: String, permissions: u16) {
+    // System files are owned by root and written regardless of the session user
+    let (parent_path, name) = utils::split_path(path).expect("Invalid system file path");
+    let directory = root.get_inode_by_path_mut(&parent_path).expect("System directory not found");
+    directory.write_file(&name, &data, false, ROOT_UID, ROOT_GID).expect("Error on writing system file");
+    if let Some(inode) = directory.get_inode_by_name_mut(&name) {
+        inode.set_permissions(permissions);
+    }
+    root.refresh_sizes(&parent_path);
+}
+
+pub fn read_users(root: &Inode) -> Vec<User> {
+    // Each line of /etc/passwd is name:uid:gid:home
+    read_system_file(root, PASSWD_PATH)
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            if fields.len() != 4 {
+                return None;
+            }
+            Some(User {
+                name: fields[0].to_string(),
+                uid: fields[1].parse().ok()?,
+                gid: fields[2].parse().ok()?,
+                home: fields[3].to_string(),
+            })
+        })
+        .collect()
+}
+
+pub fn write_users(root: &mut Inode, users: &[User]) {
+    let data: String = users
+        .iter()
+        .map(|user| format!("{}:{}:{}:{}\n", user.name, user.uid, user.gid, user.home))
+        .collect();
+    write_system_file(root, PASSWD_PATH, data, 0o644);
+}
+
+pub fn read_groups(root: &Inode) -> Vec<Group> {
+    // Each line of /etc/group is name:gid:member,member
+    read_system_file(root, GROUP_PATH)
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            if fields.len() != 3 {
+                return None;
+            }
+            Some(Group {
+                name: fields[0].to_string(),
+                gid: fields[1].parse().ok()?,
+                members: fields[2].split(',').filter(|member| !member.is_empty()).map(String::from).collect(),
+            })
+        })
+        .collect()
+}
+
+pub fn write_groups(root: &mut Inode, groups: &[Group]) {
+    let data: String = groups
+        .iter()
+        .map(|group| format!("{}:{}:{}\n", group.name, group.gid, group.members.join(",")))
+        .collect();
+    write_system_file(root, GROUP_PATH, data, 0o644);
+}
+
+pub fn find_user(root: &Inode, name: &str) -> Option<User> {
+    read_users(root).into_iter().find(|user| user.name == name)
+}
+
+pub fn find_group(root: &Inode, name: &str) -> Option<Group> {
+    read_groups(root).into_iter().find(|group| group.name == name)
+}
+
+pub fn user_name(root: &Inode, uid: u32) -> String {
+    match read_users(root).into_iter().find(|user| user.uid == uid) {
+        Some(user) => user.name,
+        None => uid.to_string(),
+    }
+}
+
+pub fn group_name(root: &Inode, gid: u32) -> String {
+    match read_groups(root).into_iter().find(|group| group.gid == gid) {
+        Some(group) => group.name,
+        None => gid.to_string(),
+    }
+}
+
+pub fn user_groups(root: &Inode, user: &User) -> Vec<u32> {
+    // The primary group of the user followed by every group listing it as a member
+    let mut groups = vec![user.gid];
+    for group in read_groups(root) {
+        if group.members.contains(&user.name) && !groups.contains(&group.gid) {
+            groups.push(group.gid);
+        }
+    }
+    groups
+}
+
+pub fn next_id(root: &Inode) -> u32 {
+    // First id, from FIRST_USER_ID on, free both as a uid and as a gid
+    let users = read_users(root);
+    let groups = read_groups(root);
+    let mut id = FIRST_USER_ID;
+    while users.iter().any(|user| user.uid == id) || groups.iter().any(|group| group.gid == id) {
+        id += 1;
+    }
+    id
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn hash_password(password: &str, salt: &str) -> String {
+    let mut hash = [0u8; 32];
+    pbkdf2_hmac::<Sha256>(password.as_bytes(), salt.as_bytes(), HASH_ROUNDS, &mut hash);
+    to_hex(&hash)
+}
+
+pub fn set_password(root: &mut Inode, name: &str, password: &str) {
+    // Each line of /etc/shadow is name:salt:hash, with an empty hash for accounts without password
+    let mut salt = [0u8; SALT_LENGTH];
+    getrandom::getrandom(&mut salt).expect("Error on generating a random salt");
+    let salt = to_hex(&salt);
+    let entry = if password.is_empty() {
+        format!("{}::", name)
+    } else {
+        format!("{}:{}:{}", name, salt, hash_password(password, &salt))
+    };
+    let mut lines: Vec<String> = read_system_file(root, SHADOW_PATH)
+        .lines()
+        .filter(|line| line.split(':').next() != Some(name))
+        .map(String::from)
+        .collect();
+    lines.push(entry);
+    write_system_file(root, SHADOW_PATH, lines.join("\n") + "\n", 0o600);
+}
+
+pub fn remove_password(root: &mut Inode, name: &str) {
+    let data: String = read_system_file(root, SHADOW_PATH)
+        .lines()
+        .filter(|line| line.split(':').next() != Some(name))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    write_system_file(root, SHADOW_PATH, data, 0o600);
+}
+
+fn shadow_entry(root: &Inode, name: &str) -> Option<(String, String)> {
+    // Salt and hash of the account
+    read_system_file(root, SHADOW_PATH).lines().find_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        (fields.len() == 3 && fields[0] == name).then(|| (fields[1].to_string(), fields[2].to_string()))
+    })
+}
+
+pub fn has_password(root: &Inode, name: &str) -> bool {
+    shadow_entry(root, name).is_some_and(|(_, hash)| !hash.is_empty())
+}
+
+pub fn verify_password(root: &Inode, name: &str, password: &str) -> bool {
+    // Accounts without a password, or locked, can't be authenticated to, whatever password is given
+    match shadow_entry(root, name) {
+        Some((salt, hash)) => !password.is_empty() && !hash.is_empty() && hash_password(password, &salt) == hash,
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bootstrap, has_password, next_id, read_groups, read_system_file, set_password, shadow_entry, verify_password, write_groups, write_users};
+    use super::{Group, User, DEFAULT_USER, FIRST_USER_ID, SHADOW_PATH};
+    use crate::session::{ROOT_GID, ROOT_UID};
+    use crate::types::{Inode, DIR_MODE};
+
+    fn setup() -> Inode {
+        let mut root = Inode::new(DIR_MODE, String::from("/"), ROOT_UID, ROOT_GID);
+        bootstrap(&mut root);
+        root
+    }
+
+    #[test]
+    fn passwords_are_salted_and_verified() {
+        let mut root = setup();
+        assert!(!has_password(&root, DEFAULT_USER));
+        set_password(&mut root, DEFAULT_USER, "secret");
+        let (salt, hash) = shadow_entry(&root, DEFAULT_USER).unwrap();
+        assert!(!read_system_file(&root, SHADOW_PATH).contains("secret"));
+        assert!(has_password(&root, DEFAULT_USER));
+        assert!(verify_password(&root, DEFAULT_USER, "secret"));
+        assert!(!verify_password(&root, DEFAULT_USER, "Secret"));
+        assert!(!verify_password(&root, "nobody", "secret"));
+
+        // The same password gets a new salt, so a new hash
+        set_password(&mut root, DEFAULT_USER, "secret");
+        let (other_salt, other_hash) = shadow_entry(&root, DEFAULT_USER).unwrap();
+        assert_ne!((salt, hash), (other_salt, other_hash));
+        assert!(verify_password(&root, DEFAULT_USER, "secret"));
+        assert_eq!(read_system_file(&root, SHADOW_PATH).lines().filter(|line| line.starts_with(DEFAULT_USER)).count(), 1);
+    }
+
+    #[test]
+    fn empty_and_locked_passwords_never_match() {
+        let mut root = setup();
+        assert!(!verify_password(&root, DEFAULT_USER, ""));
+        assert!(!verify_password(&root, "root", ""));
+        assert!(!verify_password(&root, "root", "!"));
+        set_password(&mut root, DEFAULT_USER, "");
+        assert!(!has_password(&root, DEFAULT_USER));
+        assert!(!verify_password(&root, DEFAULT_USER, ""));
+    }
+
+    #[test]
+    fn next_id_is_free_as_a_uid_and_as_a_gid() {
+        let mut root = setup();
+        assert_eq!(next_id(&root), FIRST_USER_ID + 1);
+        let mut groups = read_groups(&root);
+        groups.push(Group {
+            name: String::from("staff"),
+            gid: FIRST_USER_ID + 1,
+            members: Vec::new(),
+        });
+        write_groups(&mut root, &groups);
+        write_users(&mut root, &[User {
+            name: String::from("other"),
+            uid: FIRST_USER_ID + 2,
+            gid: FIRST_USER_ID + 1,
+            home: String::from("/home/other"),
+        }]);
+        assert_eq!(next_id(&root), FIRST_USER_ID + 3);
+    }
+}
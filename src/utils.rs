@@ -7,3 +7,59 @@ pub fn now_date() -> u64 {
         Err(_) => panic!("SystemTime before UNIX EPOCH!"),
     }
 }
+
+pub fn normalize_path(path: &str) -> Vec<String> {
+    // Split a path in its components, resolving '.' and '..'
+    let mut components: Vec<String> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {},
+            ".." => {
+                components.pop();
+            },
+            _ => components.push(component.to_string()),
+        }
+    }
+    components
+}
+
+pub fn split_path(path: &str) -> Result<(Vec<String>, String), &'static str> {
+    // Split a path in the components of its parent directory and its last name
+    if path.contains('\\') {
+        return Err("Invalid path");
+    }
+    let mut components = normalize_path(path);
+    match components.pop() {
+        Some(name) => Ok((components, name)),
+        None => Err("Invalid path"),
+    }
+}
+
+pub fn parent_path(path: &str) -> String {
+    // Path of the directory containing the path, as typed by the user
+    match path.trim_end_matches('/').rsplit_once('/') {
+        Some(("", _)) => String::from("/"),
+        Some((parent, _)) => String::from(parent),
+        None => String::from("."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_path, split_path};
+
+    #[test]
+    fn paths_are_normalized() {
+        assert_eq!(normalize_path("/home//user/./notes/"), ["home", "user", "notes"]);
+        assert_eq!(normalize_path("a/b/../c"), ["a", "c"]);
+        assert_eq!(normalize_path("/../.."), Vec::<String>::new());
+        assert!(normalize_path("/").is_empty());
+    }
+
+    #[test]
+    fn paths_are_split_in_parent_and_name() {
+        assert_eq!(split_path("/tmp/a/../b"), Ok((vec![String::from("tmp")], String::from("b"))));
+        assert!(split_path("/").is_err());
+        assert!(split_path("a\\b").is_err());
+    }
+}